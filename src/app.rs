@@ -1,9 +1,52 @@
 use anyhow::Result;
-use ratatui::widgets::ListState;
+use crossterm::event::KeyEvent;
+use ratatui::{layout::Rect, widgets::ListState};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
 
-use crate::server::Server;
+use crate::broadcast::{Rollout, RolloutState, Watch};
+use crate::config::{BroadcastOptions, Config, FormDefaults};
+use crate::driver::Driver;
+use crate::history::{History, Snippets};
+use crate::report::{file_stamp, format_timestamp, free_path, unix_secs, Report, ReportFormat, RunLog};
+use crate::pick::PickFormat;
+use crate::session::Session;
+use crate::tail::{Tail, TailLine, TailSource};
+use crate::share::ShareFormat;
+use crate::keymap::{Action, KeyChord, KeyMatch, Keymap};
+use crate::theme::Theme;
+use crate::server::{ProfileDefaults, Server, DEFAULT_BAUD, DEFAULT_SHELL};
+use crate::template;
+use crate::validate;
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+
+/// On-disk layout of a profile file.
+#[derive(Serialize, Deserialize)]
+struct ProfileFile {
+    #[serde(default)]
+    defaults: ProfileDefaults,
+    #[serde(default)]
+    servers: Vec<Server>,
+}
+
+impl ProfileFile {
+    fn parse(data: &str) -> serde_json::Result<Self> {
+        // 旧版本的 profile 只是一个服务器数组；分开解析，错误信息才能指出出错的字段
+        if data.trim_start().starts_with('[') {
+            let servers = serde_json::from_str(data)?;
+            return Ok(Self { defaults: ProfileDefaults::default(), servers });
+        }
+        serde_json::from_str(data)
+    }
+}
 
 #[derive(Serialize, Deserialize, Default)]
 struct AppState {
@@ -15,95 +58,824 @@ struct AppState {
 
 pub enum InputMode {
     Normal,
-    Adding(AddingState),
-    Editing(EditingState),
+    Form(ConnectionForm),
     ConfirmDelete(usize), // 存储要删除的服务器索引
+    ConfirmQuit,
     ShowMessage(String), // 显示提示信息
     BroadcastCommand(BroadcastState),
+    BroadcastResults(ResultsView),
+    RunHistory { runs: Vec<Report>, cursor: usize },
+    Watch(WatchView),
+    TailSetup(TailSetup),
+    Tail(TailView),
+    TmuxSelect(TargetSelection), // 选择在 tmux 窗格中打开的服务器
+    QuickConnect { input: String, error: Option<String> },
+    ConfirmSave(Server), // 快速连接后询问是否保存到当前 profile
+    Share { index: usize, format: ShareFormat },
+    Import { text: String, error: Option<String> },
     SelectingProfile,
     CreatingProfile(String),
+    Help,
+}
+
+/// Servers ticked as targets of a broadcast or a log tail.
+pub struct TargetSelection {
+    pub selected: Vec<bool>, // one bool per server
+    pub cursor: usize,       // highlighted server in selection list
+}
+
+impl TargetSelection {
+    pub fn new(server_count: usize) -> Self {
+        Self {
+            selected: vec![false; server_count],
+            cursor: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.cursor + 1 < self.selected.len() {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    /// Tick or untick a server; only servers with an ssh driver can run remote commands.
+    pub fn toggle(&mut self, idx: usize, servers: &[Server]) {
+        if servers.get(idx).is_some_and(|s| s.driver.is_ssh()) {
+            self.selected[idx] = !self.selected[idx];
+        }
+    }
+
+    /// Indices of the ticked servers.
+    pub fn indices(&self) -> Vec<usize> {
+        self.selected
+            .iter()
+            .enumerate()
+            .filter(|(_, &sel)| sel)
+            .map(|(i, _)| i)
+            .collect()
+    }
 }
 
 pub struct BroadcastState {
     pub phase: BroadcastPhase,
     pub command: String,
-    pub selected: Vec<bool>, // one bool per server
-    pub cursor: usize,       // highlighted server in selection list
+    pub targets: TargetSelection,
+    pub history_idx: Option<usize>, // 正在浏览的历史记录位置
+    draft: String,                  // 浏览历史前输入的命令
+    pub snippet_name: Option<String>, // 从片段库选取的片段名，保存时作为默认名称
+    pub options: BroadcastOptions,
+    pub watch: bool, // 以 watch 模式反复执行
 }
 
 pub enum BroadcastPhase {
     EnterCommand,
+    SearchHistory { query: String, found: Option<usize> },
+    Snippets { cursor: usize },
+    SaveSnippet(String),
     SelectServers,
+    Preview { targets: Vec<(usize, Vec<String>)>, cursor: usize }, // (服务器索引, ssh 参数)
 }
 
 impl BroadcastState {
-    pub fn new(server_count: usize) -> Self {
+    pub fn new(server_count: usize, options: BroadcastOptions) -> Self {
         Self {
             phase: BroadcastPhase::EnterCommand,
             command: String::new(),
-            selected: vec![false; server_count],
+            targets: TargetSelection::new(server_count),
+            history_idx: None,
+            draft: String::new(),
+            snippet_name: None,
+            options,
+            watch: false,
+        }
+    }
+
+    /// Recall the previous history entry, remembering what was typed so far.
+    pub fn history_previous(&mut self, history: &[String]) {
+        let idx = match self.history_idx {
+            None if history.is_empty() => return,
+            None => {
+                self.draft = self.command.clone();
+                history.len() - 1
+            }
+            Some(0) => return,
+            Some(i) => i - 1,
+        };
+        self.history_idx = Some(idx);
+        self.command = history[idx].clone();
+    }
+
+    /// Recall the next history entry, or go back to the typed command after the newest.
+    pub fn history_next(&mut self, history: &[String]) {
+        match self.history_idx {
+            None => {}
+            Some(i) if i + 1 < history.len() => {
+                self.history_idx = Some(i + 1);
+                self.command = history[i + 1].clone();
+            }
+            Some(_) => {
+                self.history_idx = None;
+                self.command = std::mem::take(&mut self.draft);
+            }
+        }
+    }
+
+    /// Replace the command, e.g. with a search result or a snippet.
+    pub fn set_command(&mut self, command: &str) {
+        self.command = command.to_string();
+        self.history_idx = None;
+    }
+}
+
+/// Progress and output of a running or finished broadcast.
+pub struct ResultsView {
+    pub rollout: Rollout,
+    pub cursor: usize,
+    pub output_scroll: Option<u16>, // Some 时显示选中主机的完整输出
+    pub grouped: bool,              // 按相同输出分组显示
+    pub notice: Option<String>,     // 导出报告等操作的结果
+    pub diff_base: Option<usize>,   // 标记为对比基准的组，记录组内的一台主机
+    pub diff: Option<(usize, usize)>, // 正在对比的两组，各记录其第一台主机
+    saved: bool,                      // 已写入运行历史
+}
+
+impl ResultsView {
+    pub fn new(rollout: Rollout) -> Self {
+        Self {
+            rollout,
             cursor: 0,
+            output_scroll: None,
+            grouped: false,
+            notice: None,
+            diff_base: None,
+            diff: None,
+            saved: false,
         }
     }
+
+    /// A run opened from the run history, which must not be saved again.
+    pub fn past(rollout: Rollout) -> Self {
+        Self {
+            saved: true,
+            ..Self::new(rollout)
+        }
+    }
+
+    /// Number of rows in the current view: hosts, or groups of identical output.
+    pub fn row_count(&self) -> usize {
+        if self.grouped {
+            self.rollout.clusters().len()
+        } else {
+            self.rollout.hosts.len()
+        }
+    }
+
+    /// Host whose output the selected row shows; for a group, its first host.
+    pub fn selected_host(&self) -> Option<usize> {
+        if self.grouped {
+            self.rollout.clusters().get(self.cursor).map(|c| c[0])
+        } else {
+            Some(self.cursor).filter(|i| *i < self.rollout.hosts.len())
+        }
+    }
+
+    pub fn toggle_grouped(&mut self) {
+        self.grouped = !self.grouped;
+        self.cursor = 0;
+        self.diff_base = None;
+    }
+
+    /// Mark the selected group as the diff base, or diff it against the marked one.
+    pub fn mark_diff(&mut self) {
+        if !self.grouped {
+            return;
+        }
+        let Some(cluster) = self.rollout.clusters().into_iter().nth(self.cursor) else {
+            return;
+        };
+        match self.diff_base {
+            Some(base) if cluster.contains(&base) => self.diff_base = None,
+            Some(base) => {
+                self.diff = Some((base, cluster[0]));
+                self.output_scroll = Some(0);
+            }
+            None => self.diff_base = Some(cluster[0]),
+        }
+    }
+
+    /// Write the run as a report to the current directory.
+    pub fn export(&mut self, format: ReportFormat) {
+        let result = std::env::current_dir()
+            .map_err(anyhow::Error::from)
+            .and_then(|dir| Report::from_rollout(&self.rollout).export(&dir, format));
+        self.notice = Some(match result {
+            Ok(path) => format!("Saved {}", path.display()),
+            Err(e) => format!("Could not save the report: {}", e),
+        });
+    }
+
+    pub fn close_output(&mut self) {
+        self.output_scroll = None;
+        self.diff = None;
+    }
 }
 
-pub struct AddingState {
-    pub field_idx: usize,
-    pub name: String,
-    pub user: String,
-    pub host: String,
-    pub port: String,
-    pub jump_host: String,
+pub enum TailPhase {
+    Source,
+    SelectServers,
 }
 
-impl AddingState {
-    pub fn new() -> Self {
+/// Choosing what to tail and on which servers.
+pub struct TailSetup {
+    pub phase: TailPhase,
+    pub source: TailSource,
+    pub target: String, // 文件路径或 journald unit
+    pub targets: TargetSelection,
+}
+
+impl TailSetup {
+    pub fn new(server_count: usize) -> Self {
         Self {
-            field_idx: 0,
-            name: String::new(),
-            user: String::new(),
-            host: String::new(),
-            port: "22".to_string(),
-            jump_host: String::new(),
+            phase: TailPhase::Source,
+            source: TailSource::File,
+            target: String::new(),
+            targets: TargetSelection::new(server_count),
+        }
+    }
+}
+
+/// The merged log stream of several servers.
+pub struct TailView {
+    pub tail: Tail,
+    pub scroll: usize,                // 距底部的行数，0 表示跟随最新行
+    pub filter: Option<Regex>,
+    pub filter_input: Option<String>, // Some 时正在输入过滤条件
+    pub notice: Option<String>,
+}
+
+impl TailView {
+    pub fn new(tail: Tail) -> Self {
+        Self {
+            tail,
+            scroll: 0,
+            filter: None,
+            filter_input: None,
+            notice: None,
+        }
+    }
+
+    /// Lines that pass the filter, oldest first.
+    pub fn visible(&self) -> Vec<&TailLine> {
+        self.tail
+            .lines
+            .iter()
+            .filter(|line| self.filter.as_ref().is_none_or(|re| re.is_match(&line.text)))
+            .collect()
+    }
+
+    /// Scroll towards older lines, at most to the first one.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.visible().len());
+    }
+
+    /// Apply the typed filter; an empty one shows every line again.
+    pub fn apply_filter(&mut self) {
+        let Some(input) = self.filter_input.take() else {
+            return;
+        };
+        if input.is_empty() {
+            self.filter = None;
+            return;
+        }
+        match Regex::new(&input) {
+            Ok(re) => {
+                self.filter = Some(re);
+                self.scroll = 0;
+            }
+            Err(e) => self.notice = Some(format!("Invalid filter: {}", e)),
+        }
+    }
+
+    /// Write the shown lines, prefixed with their server, to a file in the current directory.
+    pub fn save(&mut self) {
+        let result = std::env::current_dir().map_err(anyhow::Error::from).and_then(|dir| {
+            let stamp = file_stamp(&format_timestamp(unix_secs(SystemTime::now())));
+            let path = free_path(&dir, &format!("sshx-tail-{}", stamp), "log");
+            let mut text = String::new();
+            for line in self.visible() {
+                text.push_str(&format!("{} | {}\n", self.tail.hosts[line.host].name, line.text));
+            }
+            fs::write(&path, text)?;
+            Ok(path)
+        });
+        self.notice = Some(match result {
+            Ok(path) => format!("Saved {}", path.display()),
+            Err(e) => format!("Could not save the log: {}", e),
+        });
+    }
+}
+
+/// Live table of a command that is re-run every few seconds.
+pub struct WatchView {
+    pub watch: Watch,
+    pub cursor: usize,
+    pub output_scroll: Option<u16>, // Some 时显示选中主机的输出和变化
+}
+
+impl WatchView {
+    pub fn new(watch: Watch) -> Self {
+        Self {
+            watch,
+            cursor: 0,
+            output_scroll: None,
         }
     }
 }
 
-pub struct EditingState {
-    pub server_index: usize,
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FormField {
+    Name,
+    Driver,
+    User,
+    Host,
+    Port,
+    JumpHost,
+    IdentityFile,
+    SshOptions,
+    Target,
+    Namespace,
+    Baud,
+    Command,
+    Vars,
+}
+
+impl FormField {
+    /// Field title; some fields mean different things depending on the driver.
+    pub fn label(self, driver: Driver) -> &'static str {
+        match self {
+            FormField::Name => "Name",
+            FormField::Driver => "Driver (Space/←/→ to change)",
+            FormField::User if driver == Driver::Docker => "User (inside the container)",
+            FormField::User => "User",
+            FormField::Host => "Host/IP",
+            FormField::Port => "Port",
+            FormField::JumpHost => "Jump Host (e.g. user@host:port, 'none' to disable)",
+            FormField::IdentityFile => "Identity File",
+            FormField::SshOptions => "SSH Options (Key=Value, space separated)",
+            FormField::Target => match driver {
+                Driver::Serial => "Serial Device (e.g. /dev/ttyUSB0)",
+                Driver::Docker => "Container",
+                _ => "Pod",
+            },
+            FormField::Namespace => "Namespace",
+            FormField::Baud => "Baud Rate",
+            FormField::Command if driver == Driver::Custom => {
+                "Command ({user} {host} {port} {jump} {name})"
+            }
+            FormField::Command => "Shell",
+            FormField::Vars => "Variables for command templates (key=value, space separated)",
+        }
+    }
+}
+
+const DEFAULTS_FIELDS: [FormField; 5] = [
+    FormField::User,
+    FormField::Port,
+    FormField::JumpHost,
+    FormField::IdentityFile,
+    FormField::SshOptions,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FormKind {
+    Add,
+    Edit(usize), // 正在编辑的服务器索引
+    Defaults,    // 编辑当前 profile 的默认值
+}
+
+/// State of the add/edit connection form and the profile defaults form.
+pub struct ConnectionForm {
+    pub kind: FormKind,
+    pub driver: Driver,
     pub field_idx: usize,
-    pub name: String,
-    pub user: String,
-    pub host: String,
-    pub port: String,
-    pub jump_host: String,
+    pub values: HashMap<FormField, String>,
+    pub errors: HashMap<FormField, String>,
 }
 
-impl EditingState {
-    pub fn new(server: &Server, index: usize) -> Self {
+impl ConnectionForm {
+    pub fn new() -> Self {
+        Self {
+            kind: FormKind::Add,
+            driver: Driver::Ssh,
+            field_idx: 0,
+            values: HashMap::new(),
+            errors: HashMap::new(),
+        }
+    }
+
+    pub fn edit(server: &Server, index: usize) -> Self {
+        let mut values = HashMap::new();
+        values.insert(FormField::Name, server.name.clone());
+        values.insert(FormField::User, server.user.clone());
+        values.insert(FormField::Host, server.host.clone());
+        values.insert(FormField::Port, server.port.map(|p| p.to_string()).unwrap_or_default());
+        values.insert(FormField::JumpHost, server.jump_host.clone());
+        values.insert(FormField::IdentityFile, server.identity_file.clone());
+        values.insert(FormField::SshOptions, shell_words::join(&server.ssh_options));
+        values.insert(FormField::Target, server.target.clone());
+        values.insert(FormField::Namespace, server.namespace.clone());
+        values.insert(FormField::Baud, server.baud.map(|b| b.to_string()).unwrap_or_default());
+        values.insert(FormField::Command, server.command.clone());
+        let vars: Vec<String> = server.vars.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        values.insert(FormField::Vars, shell_words::join(vars));
+        Self {
+            kind: FormKind::Edit(index),
+            driver: server.driver,
+            field_idx: 0,
+            values,
+            errors: HashMap::new(),
+        }
+    }
+
+    /// The add form filled in from a server that is not saved yet.
+    pub fn prefilled(server: &Server) -> Self {
         Self {
-            server_index: index,
+            kind: FormKind::Add,
+            ..Self::edit(server, 0)
+        }
+    }
+
+    pub fn defaults(defaults: &ProfileDefaults) -> Self {
+        let mut values = HashMap::new();
+        values.insert(FormField::User, defaults.user.clone());
+        values.insert(FormField::Port, defaults.port.map(|p| p.to_string()).unwrap_or_default());
+        values.insert(FormField::JumpHost, defaults.jump_host.clone());
+        values.insert(FormField::IdentityFile, defaults.identity_file.clone());
+        values.insert(FormField::SshOptions, shell_words::join(&defaults.ssh_options));
+        Self {
+            kind: FormKind::Defaults,
+            driver: Driver::Ssh,
             field_idx: 0,
-            name: server.name.clone(),
-            user: server.user.clone(),
-            host: server.host.clone(),
-            port: server.port.clone(),
-            jump_host: server.jump_host.clone(),
+            values,
+            errors: HashMap::new(),
         }
     }
+
+    /// Fields shown for the current kind and driver, in display order.
+    pub fn fields(&self) -> Vec<FormField> {
+        match self.kind {
+            FormKind::Defaults => DEFAULTS_FIELDS.to_vec(),
+            FormKind::Add | FormKind::Edit(_) => {
+                let mut fields = vec![FormField::Name, FormField::Driver];
+                fields.extend_from_slice(self.driver.fields());
+                fields
+            }
+        }
+    }
+
+    pub fn current_field(&self) -> FormField {
+        self.fields()[self.field_idx]
+    }
+
+    pub fn value(&self, field: FormField) -> &str {
+        self.values.get(&field).map(String::as_str).unwrap_or("")
+    }
+
+    /// The value that gets saved: fields hidden by the current driver count as empty, but
+    /// what was typed into them stays in the form in case the driver is switched back.
+    fn saved_value(&self, field: FormField) -> &str {
+        if self.fields().contains(&field) {
+            self.value(field)
+        } else {
+            ""
+        }
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        let field = self.current_field();
+        if field == FormField::Driver {
+            if c == ' ' {
+                self.set_driver(self.driver.next());
+            }
+            return;
+        }
+        self.values.entry(field).or_default().push(c);
+        self.errors.remove(&field);
+    }
+
+    pub fn pop_char(&mut self) {
+        let field = self.current_field();
+        if let Some(value) = self.values.get_mut(&field) {
+            value.pop();
+        }
+        self.errors.remove(&field);
+    }
+
+    /// Switch drivers while the Driver field is focused; values of hidden fields are kept
+    /// so that switching back restores them, but they are not saved.
+    pub fn set_driver(&mut self, driver: Driver) {
+        self.driver = driver;
+        self.errors.clear();
+    }
+
+    pub fn next_field(&mut self) {
+        self.field_idx = (self.field_idx + 1) % self.fields().len();
+    }
+
+    pub fn previous_field(&mut self) {
+        let len = self.fields().len();
+        self.field_idx = (self.field_idx + len - 1) % len;
+    }
+
+    /// Validate every field and build the server, recording per-field errors on failure.
+    pub fn validate_server(&mut self, servers: &[Server]) -> Option<Server> {
+        self.errors.clear();
+        let visible = self.fields();
+        let driver = self.driver;
+        let name = self.saved_value(FormField::Name).trim().to_string();
+        // 保存时去掉 IPv6 地址的方括号，否则命令行会变成 root@[::1]
+        let host = validate::unbracket(self.saved_value(FormField::Host).trim()).to_string();
+        let target = self.saved_value(FormField::Target).trim().to_string();
+        let namespace = self.saved_value(FormField::Namespace).trim().to_string();
+        let baud = self.saved_value(FormField::Baud).trim().to_string();
+        let command = self.saved_value(FormField::Command).trim().to_string();
+
+        if name.is_empty() {
+            self.errors.insert(FormField::Name, "Name is required".to_string());
+        } else if servers
+            .iter()
+            .enumerate()
+            .any(|(i, s)| s.name == name && self.kind != FormKind::Edit(i))
+        {
+            self.errors.insert(FormField::Name, format!("'{}' already exists in this profile", name));
+        }
+        if visible.contains(&FormField::Host) {
+            if let Err(e) = validate::check_host(&host) {
+                self.errors.insert(FormField::Host, e);
+            }
+        }
+        for &field in driver.required() {
+            if self.saved_value(field).trim().is_empty() {
+                self.errors
+                    .insert(field, format!("{} is required", field.label(driver)));
+            }
+        }
+        if target.contains(char::is_whitespace) && driver != Driver::Serial {
+            self.errors.insert(
+                FormField::Target,
+                format!("{} must not contain spaces", FormField::Target.label(driver)),
+            );
+        }
+        if namespace.contains(char::is_whitespace) {
+            self.errors.insert(FormField::Namespace, "Namespace must not contain spaces".to_string());
+        }
+        let baud = if baud.is_empty() {
+            None
+        } else {
+            match baud.parse::<u32>() {
+                Ok(baud) if baud > 0 => Some(baud),
+                _ => {
+                    self.errors.insert(FormField::Baud, format!("Invalid baud rate '{}'", baud));
+                    None
+                }
+            }
+        };
+        let vars = self.validate_vars();
+        let inherited = self.validate_inherited();
+
+        let server = Server {
+            name,
+            driver,
+            user: inherited.user,
+            host,
+            port: inherited.port,
+            jump_host: inherited.jump_host,
+            identity_file: inherited.identity_file,
+            ssh_options: inherited.ssh_options,
+            target,
+            namespace,
+            baud,
+            command,
+            vars,
+        };
+        let command_check = match driver {
+            Driver::Custom if !server.command.is_empty() => {
+                server.to_custom_args(&ProfileDefaults::default()).map(drop)
+            }
+            Driver::Docker => server.to_docker_args().map(drop),
+            Driver::Kubectl => server.to_kubectl_args().map(drop),
+            _ => Ok(()),
+        };
+        if let Err(e) = command_check {
+            self.errors.insert(FormField::Command, e);
+        }
+
+        if !self.focus_first_error() {
+            return None;
+        }
+        Some(server)
+    }
+
+    fn validate_vars(&mut self) -> BTreeMap<String, String> {
+        let words = match shell_words::split(self.saved_value(FormField::Vars)) {
+            Ok(words) => words,
+            Err(e) => {
+                self.errors.insert(FormField::Vars, format!("Invalid quoting: {}", e));
+                return BTreeMap::new();
+            }
+        };
+        let mut vars = BTreeMap::new();
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                self.errors.insert(FormField::Vars, format!("'{}' is not in key=value form", word));
+                break;
+            };
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                self.errors.insert(
+                    FormField::Vars,
+                    format!("'{}' is not a valid name (letters, digits, '_' and '-')", key),
+                );
+                break;
+            }
+            if template::SERVER_PLACEHOLDERS.contains(&key) {
+                self.errors.insert(FormField::Vars, format!("'{}' is a built-in placeholder", key));
+                break;
+            }
+            vars.insert(key.to_string(), value.to_string());
+        }
+        vars
+    }
+
+    /// Validate the fields shared with the profile defaults form.
+    pub fn validate_defaults(&mut self) -> Option<ProfileDefaults> {
+        self.errors.clear();
+        let defaults = self.validate_inherited();
+        if self.focus_first_error() {
+            Some(defaults)
+        } else {
+            None
+        }
+    }
+
+    fn validate_inherited(&mut self) -> ProfileDefaults {
+        let user = self.saved_value(FormField::User).trim().to_string();
+        let port = self.saved_value(FormField::Port).trim().to_string();
+        let jump_host = self.saved_value(FormField::JumpHost).trim().to_string();
+        let identity_file = self.saved_value(FormField::IdentityFile).trim().to_string();
+        // 选项值可以带空格，如 ProxyCommand，需要用引号括起来
+        let ssh_options = match shell_words::split(self.saved_value(FormField::SshOptions)) {
+            Ok(options) => options,
+            Err(e) => {
+                self.errors.insert(FormField::SshOptions, format!("Invalid quoting: {}", e));
+                Vec::new()
+            }
+        };
+
+        if let Err(e) = validate::check_user(&user) {
+            self.errors.insert(FormField::User, e);
+        }
+        let port = if port.is_empty() {
+            None
+        } else {
+            match validate::parse_port(&port) {
+                Ok(port) => Some(port),
+                Err(e) => {
+                    self.errors.insert(FormField::Port, e);
+                    None
+                }
+            }
+        };
+        if jump_host != "none" {
+            if let Err(e) = validate::check_jump_host(&jump_host) {
+                self.errors.insert(FormField::JumpHost, e);
+            }
+        }
+        if let Some(e) = ssh_options.iter().find_map(|o| validate::check_ssh_option(o).err()) {
+            self.errors.insert(FormField::SshOptions, e);
+        }
+
+        ProfileDefaults {
+            user,
+            port,
+            jump_host,
+            identity_file,
+            ssh_options,
+        }
+    }
+
+    /// Move the cursor to the first invalid field; returns true when there is none.
+    fn focus_first_error(&mut self) -> bool {
+        match self.fields().iter().position(|f| self.errors.contains_key(f)) {
+            Some(idx) => {
+                self.field_idx = idx;
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Value a field falls back to when left empty, shown as a placeholder.
+    pub fn placeholder(
+        &self,
+        field: FormField,
+        defaults: &ProfileDefaults,
+        fallback: &FormDefaults,
+    ) -> Option<String> {
+        // telnet 和 docker 只在显式填写时传递用户，et 和 telnet 使用自己的默认端口
+        match (self.driver, field) {
+            (Driver::Telnet | Driver::Docker, FormField::User) => return None,
+            (Driver::Et | Driver::Telnet, FormField::Port) => {
+                return self.driver.default_port().map(|p| format!("{} (default)", p));
+            }
+            (_, FormField::Baud) => return Some(format!("{} (default)", DEFAULT_BAUD)),
+            (Driver::Docker | Driver::Kubectl, FormField::Command) => {
+                return Some(format!("{} (default)", DEFAULT_SHELL));
+            }
+            _ => {}
+        }
+        let inherited = match self.kind {
+            FormKind::Defaults => None,
+            FormKind::Add | FormKind::Edit(_) => match field {
+                FormField::User if !defaults.user.is_empty() => Some(defaults.user.clone()),
+                FormField::Port => defaults.port.map(|p| p.to_string()),
+                FormField::JumpHost if !defaults.jump_host.is_empty() => Some(defaults.jump_host.clone()),
+                FormField::IdentityFile if !defaults.identity_file.is_empty() => {
+                    Some(defaults.identity_file.clone())
+                }
+                FormField::SshOptions if !defaults.ssh_options.is_empty() => {
+                    Some(shell_words::join(&defaults.ssh_options))
+                }
+                _ => None,
+            },
+        };
+        if let Some(value) = inherited {
+            return Some(format!("{} (inherited)", value));
+        }
+        match field {
+            FormField::User => Some(format!("{} (default)", fallback.user)),
+            FormField::Port => Some(format!("{} (default)", fallback.port)),
+            _ => None,
+        }
+    }
+}
+
+/// Screen areas from the last draw, used to map mouse clicks to items.
+#[derive(Default)]
+pub struct ClickAreas {
+    pub server_list: Rect,     // 服务器列表（不含边框）
+    pub popup_list: Rect,      // 广播/Profile 弹窗中的列表
+    pub popup_offset: usize,   // 弹窗列表的滚动偏移
+    pub form_fields: Vec<Rect>,
+    pub tab_bar: Vec<Rect>,    // 标签栏中的服务器列表和各标签页
+}
+
+impl ClickAreas {
+    /// Index of the list item under a screen position.
+    pub fn item_at(area: Rect, offset: usize, column: u16, row: u16) -> Option<usize> {
+        let inside = column >= area.x
+            && column < area.x + area.width
+            && row >= area.y
+            && row < area.y + area.height;
+        inside.then(|| offset + (row - area.y) as usize)
+    }
 }
 
 pub struct App {
     pub servers: Vec<Server>,
     pub state: ListState,
     pub input_mode: InputMode,
-    pub pending_g: bool,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub pending_keys: Vec<KeyChord>, // 多键序列（如 gg）中已按下的键
+    pub click_areas: ClickAreas,
+    pub last_click: Option<(Instant, usize)>, // 上次单击的时间和列表项，用于识别双击
     pub profiles: Vec<String>,
     pub profile_state: ListState,
     pub current_profile: String,
+    pub defaults: ProfileDefaults,
+    pub config: Config,
+    pub history: History,
+    pub snippets: Snippets,
+    pub runs: RunLog,
+    pub sessions: Vec<Session>,
+    pub active_tab: Option<usize>, // None 时显示服务器列表
+    pub tab_prefix: bool,          // 已按下前缀键，等待切换命令
+    /// Command that replaces sshx once the TUI is closed (`launch = "exec"`).
+    pub exec: Option<(String, Vec<String>)>,
+    /// Set by `sshx pick`: Enter prints the server in this format and quits.
+    pub pick: Option<PickFormat>,
+    pub picked: Option<String>,
+    /// Short confirmation shown in a corner until it expires, e.g. after copying.
+    pub toast: Option<(String, Instant)>,
     config_dir: PathBuf,
     state_path: PathBuf,
     last_connected: Option<String>,
+    /// Why the current profile could not be parsed; saving is refused so the file is kept.
+    load_error: Option<String>,
 }
 
 impl App {
@@ -114,6 +886,14 @@ impl App {
             fs::create_dir_all(&app_config_dir)?;
         }
         let state_path = app_config_dir.join("state.json");
+        // 历史记录和片段放在数据目录，不与 profile 混在一起
+        let data_dir = dirs::data_dir()
+            .map(|dir| dir.join("sshx"))
+            .unwrap_or_else(|| app_config_dir.clone());
+
+        let config = Config::load(&app_config_dir)?;
+        let keymap = Keymap::new(&config.keys, &config.actions)?;
+        let theme = Theme::load(config.theme.as_deref(), &config.themes)?;
 
         // Scan for profiles
         let mut profiles = Vec::new();
@@ -146,31 +926,72 @@ impl App {
             servers: Vec::new(),
             state: ListState::default(),
             input_mode: InputMode::Normal,
-            pending_g: false,
+            keymap,
+            theme,
+            pending_keys: Vec::new(),
+            click_areas: ClickAreas::default(),
+            last_click: None,
             profiles,
             profile_state: ListState::default(),
             current_profile: current_profile.clone(),
+            defaults: ProfileDefaults::default(),
+            config,
+            history: History::load(&data_dir),
+            snippets: Snippets::load(&data_dir),
+            runs: RunLog::new(&data_dir),
+            sessions: Vec::new(),
+            active_tab: None,
+            tab_prefix: false,
+            exec: None,
+            pick: None,
+            picked: None,
+            toast: None,
             config_dir: app_config_dir,
             state_path,
             last_connected: app_state.last_connected,
+            load_error: None,
         };
 
         app.load_profile(&current_profile)?;
+        // 历史和片段文件损坏时同样提示，并且不会被覆盖
+        let errors: Vec<&str> = [app.history.load_error(), app.snippets.load_error()].into_iter().flatten().collect();
+        if !errors.is_empty() {
+            let mut message = match &app.input_mode {
+                InputMode::ShowMessage(profile_error) => format!("{}\n\n", profile_error),
+                _ => String::new(),
+            };
+            message.push_str(&format!("{}\n\nFix the file and restart sshx; until then it is not saved.", errors.join("\n")));
+            app.input_mode = InputMode::ShowMessage(message);
+        }
 
         Ok(app)
     }
 
+    /// Load a profile. A file that does not parse leaves the list empty and shows why.
     pub fn load_profile(&mut self, profile_name: &str) -> Result<()> {
         let config_path = self.config_dir.join(profile_name);
-        let mut servers: Vec<Server> = if config_path.exists() {
+        self.load_error = None;
+        let (defaults, mut servers) = if config_path.exists() {
             let data = fs::read_to_string(&config_path)?;
-            serde_json::from_str(&data).unwrap_or_else(|_| Vec::new())
+            match ProfileFile::parse(&data) {
+                Ok(ProfileFile { defaults, servers }) => (defaults, servers),
+                Err(e) => {
+                    // 解析失败时不能保存，否则空列表会覆盖掉原文件
+                    let error = format!("Could not load {}: {}", config_path.display(), e);
+                    self.input_mode = InputMode::ShowMessage(format!(
+                        "{}\n\nFix the file and select the profile again; until then changes are not saved.",
+                        error
+                    ));
+                    self.load_error = Some(error);
+                    (ProfileDefaults::default(), Vec::new())
+                }
+            }
         } else {
-            Vec::new()
+            (ProfileDefaults::default(), Vec::new())
         };
 
         // Reorder: move last-connected server to the top
-        if let Some(ref key) = self.last_connected {
+        if let Some(key) = self.last_connected.as_ref().filter(|_| self.config.reorder_on_connect) {
             if let Some(pos) = servers.iter().position(|s| Self::server_key(s) == *key) {
                 if pos > 0 {
                     let server = servers.remove(pos);
@@ -180,6 +1001,7 @@ impl App {
         }
 
         self.servers = servers;
+        self.defaults = defaults;
         self.current_profile = profile_name.to_string();
         self.state = ListState::default();
         if !self.servers.is_empty() {
@@ -191,8 +1013,15 @@ impl App {
     }
 
     pub fn save(&self) -> Result<()> {
+        if let Some(error) = &self.load_error {
+            anyhow::bail!("{}", error);
+        }
         let config_path = self.config_dir.join(&self.current_profile);
-        let data = serde_json::to_string_pretty(&self.servers)?;
+        let profile = ProfileFile {
+            defaults: self.defaults.clone(),
+            servers: self.servers.clone(),
+        };
+        let data = serde_json::to_string_pretty(&profile)?;
         fs::write(config_path, data)?;
         Ok(())
     }
@@ -243,8 +1072,46 @@ impl App {
         }
     }
 
+    /// Feed a key press into the keymap, returning the action once a binding is complete.
+    pub fn resolve_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.pending_keys.push(KeyChord::from(key));
+        match self.keymap.resolve(&self.pending_keys) {
+            KeyMatch::Action(action) => {
+                self.pending_keys.clear();
+                Some(action)
+            }
+            KeyMatch::Pending => None,
+            KeyMatch::None => {
+                // 序列被打断时，单独用最后一个键重新匹配
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if retry {
+                    self.resolve_key(key)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Record a click on a list item, returning true if it completes a double-click.
+    pub fn register_click(&mut self, item: usize) -> bool {
+        let now = Instant::now();
+        let double = matches!(
+            self.last_click,
+            Some((at, last)) if last == item && now.duration_since(at) < DOUBLE_CLICK_INTERVAL
+        );
+        self.last_click = if double { None } else { Some((now, item)) };
+        double
+    }
+
     fn server_key(server: &Server) -> String {
-        format!("{}@{}:{}", server.user, server.host, server.port)
+        if !server.driver.is_ssh() {
+            // 非 ssh 服务器可能没有 host，用名字区分
+            return format!("{}:{}", server.driver.name(), server.name);
+        }
+        let port = server.port.map(|p| p.to_string()).unwrap_or_default();
+        format!("{}@{}:{}", server.user, server.host, port)
     }
 
     pub fn next_profile(&mut self) {
@@ -302,11 +1169,33 @@ impl App {
         Ok(())
     }
 
+    /// Add imported servers to the current profile, renaming any whose name is taken, and
+    /// select the first of them.
+    pub fn import_servers(&mut self, servers: Vec<Server>) -> Result<()> {
+        let first = self.servers.len();
+        for mut server in servers {
+            let base = server.name.clone();
+            let mut n = 2;
+            while self.servers.iter().any(|s| s.name == server.name) {
+                server.name = format!("{}-{}", base, n);
+                n += 1;
+            }
+            self.servers.push(server);
+        }
+        if first < self.servers.len() {
+            self.state.select(Some(first));
+        }
+        self.save()
+    }
+
     pub fn set_last_connected(&mut self, server: &Server) {
         let key = Self::server_key(server);
         self.last_connected = Some(key.clone());
         let _ = self.save_state();
 
+        if !self.config.reorder_on_connect {
+            return;
+        }
         // Reorder: move the connected server to the top
         if let Some(pos) = self.servers.iter().position(|s| Self::server_key(s) == key) {
             if pos > 0 {
@@ -317,4 +1206,92 @@ impl App {
         }
         self.state.select(Some(0));
     }
+
+    /// True while something runs in the background and the screen has to refresh on its own.
+    pub fn is_busy(&self) -> bool {
+        if !self.sessions.is_empty() || self.toast.is_some() {
+            return true;
+        }
+        match &self.input_mode {
+            InputMode::BroadcastResults(view) => view.rollout.is_active(),
+            InputMode::Watch(_) | InputMode::Tail(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn show_toast(&mut self, message: String) {
+        self.toast = Some((message, Instant::now()));
+    }
+
+    /// Pick up background progress between key presses; true if a tab has new output.
+    pub fn tick(&mut self) -> bool {
+        if self.toast.as_ref().is_some_and(|(_, at)| at.elapsed() >= TOAST_DURATION) {
+            self.toast = None;
+        }
+        let mut output = false;
+        for session in &mut self.sessions {
+            output |= session.poll();
+        }
+        if let InputMode::Watch(view) = &mut self.input_mode {
+            view.watch.poll();
+        }
+        if let InputMode::Tail(view) = &mut self.input_mode {
+            view.tail.poll();
+        }
+        if let InputMode::BroadcastResults(view) = &mut self.input_mode {
+            view.rollout.poll();
+        }
+        self.save_finished_run();
+        output
+    }
+
+    /// Add a broadcast to the run history once it has finished or been stopped and no host
+    /// is still running. Called after every tick and key press, since a stopped run may no
+    /// longer be ticked.
+    pub fn save_finished_run(&mut self) {
+        let InputMode::BroadcastResults(view) = &mut self.input_mode else {
+            return;
+        };
+        let done = matches!(view.rollout.state, RolloutState::Finished | RolloutState::Stopped);
+        if view.saved || !done || view.rollout.is_active() {
+            return;
+        }
+        view.saved = true;
+        if let Err(e) = self.runs.save(&Report::from_rollout(&view.rollout)) {
+            view.notice = Some(format!("Could not save the run: {}", e));
+        }
+    }
+
+    /// Show the server list (0) or a tab (1 and up); positions without a tab are ignored.
+    pub fn show_tab(&mut self, position: usize) {
+        if position <= self.sessions.len() {
+            self.active_tab = position.checked_sub(1);
+        }
+    }
+
+    /// Move to the next or previous tab, with the server list before the first one.
+    pub fn cycle_tab(&mut self, forward: bool) {
+        let count = self.sessions.len() + 1;
+        let current = self.active_tab.map_or(0, |i| i + 1);
+        let position = if forward { current + 1 } else { current + count - 1 };
+        self.show_tab(position % count);
+    }
+
+    /// End a tab's session and go back to the server list.
+    pub fn close_tab(&mut self, index: usize) {
+        if index < self.sessions.len() {
+            self.sessions.remove(index);
+        }
+        self.active_tab = None;
+    }
+
+    /// Profile defaults with the user and port fallbacks from `config.toml` filled in.
+    pub fn connection_defaults(&self) -> ProfileDefaults {
+        let mut defaults = self.defaults.clone();
+        if defaults.user.is_empty() {
+            defaults.user = self.config.form.user.clone();
+        }
+        defaults.port = defaults.port.or(Some(self.config.form.port));
+        defaults
+    }
 }
\ No newline at end of file
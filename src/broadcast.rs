@@ -0,0 +1,518 @@
+use std::{
+    cmp::Reverse,
+    io::{self, Read},
+    process::{Child, Command, Output, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::config::{BroadcastOptions, OnFailure};
+use crate::report::{format_timestamp, unix_secs};
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum HostStatus {
+    Pending,
+    Running,
+    Exited(i32),
+    Killed,        // 被信号终止，没有退出码
+    Error(String), // 无法启动程序
+    Skipped,
+}
+
+impl HostStatus {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, HostStatus::Exited(code) if *code != 0)
+            || matches!(self, HostStatus::Killed | HostStatus::Error(_))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, HostStatus::Pending | HostStatus::Running)
+    }
+}
+
+/// One target of a broadcast and what its command produced.
+pub struct HostRun {
+    pub name: String,
+    pub args: Vec<String>,
+    pub status: HostStatus,
+    pub stdout: String,
+    pub stderr: String,
+    pub started: Option<Instant>,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RolloutState {
+    Running,
+    Pausing(Instant), // 下一批开始的时间
+    AwaitingDecision, // 本批有失败，等待用户选择继续或停止
+    Stopped,
+    Finished,
+}
+
+/// A broadcast running in the background, batch by batch.
+pub struct Rollout {
+    pub command: String,
+    pub hosts: Vec<HostRun>,
+    pub options: BroadcastOptions,
+    pub state: RolloutState,
+    pub timestamp: String, // 开始时间，见 report::format_timestamp
+    pub batch: usize, // 已启动的批次数
+    batch_start: usize,
+    next: usize,
+    processes: Processes,
+}
+
+impl Rollout {
+    /// Start the first batch; `targets` are (server name, program arguments).
+    pub fn start(
+        program: &str,
+        command: &str,
+        targets: Vec<(String, Vec<String>)>,
+        options: BroadcastOptions,
+    ) -> Self {
+        let hosts: Vec<HostRun> = targets
+            .into_iter()
+            .map(|(name, args)| HostRun {
+                name,
+                args,
+                status: HostStatus::Pending,
+                stdout: String::new(),
+                stderr: String::new(),
+                started: None,
+                duration: None,
+            })
+            .collect();
+        let mut rollout = Self {
+            command: command.to_string(),
+            processes: Processes::new(program, hosts.len()),
+            hosts,
+            options,
+            state: RolloutState::Running,
+            timestamp: format_timestamp(unix_secs(SystemTime::now())),
+            batch: 0,
+            batch_start: 0,
+            next: 0,
+        };
+        rollout.start_batch();
+        rollout
+    }
+
+    /// A finished run loaded from the run history, shown without running anything.
+    pub fn finished(command: String, hosts: Vec<HostRun>, timestamp: String) -> Self {
+        let next = hosts.len();
+        Self {
+            command,
+            hosts,
+            options: BroadcastOptions::default(),
+            state: RolloutState::Finished,
+            timestamp,
+            batch: 1,
+            batch_start: 0,
+            next,
+            processes: Processes::new("", 0),
+        }
+    }
+
+    fn batch_size(&self) -> usize {
+        if self.options.batch_size == 0 {
+            self.hosts.len().max(1)
+        } else {
+            self.options.batch_size
+        }
+    }
+
+    pub fn batch_count(&self) -> usize {
+        self.hosts.len().div_ceil(self.batch_size())
+    }
+
+    fn start_batch(&mut self) {
+        let end = (self.next + self.batch_size()).min(self.hosts.len());
+        for i in self.next..end {
+            self.processes.spawn(&mut self.hosts[i], i);
+        }
+        self.batch_start = self.next;
+        self.next = end;
+        self.batch += 1;
+        self.state = RolloutState::Running;
+    }
+
+    /// Collect finished hosts and move on to the next batch when it is due.
+    pub fn poll(&mut self) {
+        for (i, output) in self.processes.poll() {
+            record(&self.processes.program, &mut self.hosts[i], output);
+        }
+
+        match self.state {
+            RolloutState::Running if !self.batch_running() => {
+                let failed = self.hosts[self.batch_start..self.next]
+                    .iter()
+                    .any(|h| h.status.is_failure());
+                if self.next >= self.hosts.len() {
+                    self.state = RolloutState::Finished;
+                } else if failed {
+                    match self.options.on_failure {
+                        OnFailure::Stop => self.stop(),
+                        OnFailure::Ask => self.state = RolloutState::AwaitingDecision,
+                        OnFailure::Continue => self.advance(),
+                    }
+                } else {
+                    self.advance();
+                }
+            }
+            RolloutState::Pausing(until) if Instant::now() >= until => self.start_batch(),
+            _ => {}
+        }
+    }
+
+    fn advance(&mut self) {
+        if self.options.pause == 0 {
+            self.start_batch();
+        } else {
+            self.state = RolloutState::Pausing(Instant::now() + Duration::from_secs(self.options.pause));
+        }
+    }
+
+    /// Go on with the next batch after a failure.
+    pub fn resume(&mut self) {
+        if self.state == RolloutState::AwaitingDecision {
+            self.advance();
+        }
+    }
+
+    /// Skip every host that has not started and kill the commands still running.
+    pub fn stop(&mut self) {
+        for host in &mut self.hosts[self.next..] {
+            host.status = HostStatus::Skipped;
+        }
+        // 被终止的主机在下一次 poll 时记为 Killed
+        self.processes.kill();
+        self.next = self.hosts.len();
+        self.state = RolloutState::Stopped;
+    }
+
+    fn batch_running(&self) -> bool {
+        self.hosts.iter().any(|h| matches!(h.status, HostStatus::Running))
+    }
+
+    /// True while results may still change, so the UI has to keep polling.
+    pub fn is_active(&self) -> bool {
+        matches!(self.state, RolloutState::Running | RolloutState::Pausing(_)) || self.batch_running()
+    }
+
+    /// Hosts that finished (including skipped ones) and hosts that failed.
+    pub fn counts(&self) -> (usize, usize) {
+        let done = self.hosts.iter().filter(|h| h.status.is_finished()).count();
+        let failed = self.hosts.iter().filter(|h| h.status.is_failure()).count();
+        (done, failed)
+    }
+
+    /// Finished hosts grouped by exit status and stdout, largest group first.
+    pub fn clusters(&self) -> Vec<Vec<usize>> {
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        for (i, host) in self.hosts.iter().enumerate() {
+            if !host.status.is_finished() {
+                continue;
+            }
+            let same = |c: &&mut Vec<usize>| {
+                let first = &self.hosts[c[0]];
+                first.status == host.status && first.stdout == host.stdout
+            };
+            match clusters.iter_mut().find(same) {
+                Some(cluster) => cluster.push(i),
+                None => clusters.push(vec![i]),
+            }
+        }
+        // sort_by_key 是稳定排序，同样大小的组保持主机顺序
+        clusters.sort_by_key(|c| Reverse(c.len()));
+        clusters
+    }
+}
+
+/// Output of a host's command: (stdout, stderr), or why it could not be started.
+type Captured = io::Result<(Vec<u8>, Vec<u8>)>;
+
+/// The commands started for each host. The processes are kept so they can be killed when
+/// the run is stopped or its window is closed.
+struct Processes {
+    program: String,
+    children: Vec<Option<Child>>,
+    captured: Vec<Option<Captured>>, // 输出已读完、等待进程退出的主机
+    tx: Sender<(usize, Captured)>,
+    rx: Receiver<(usize, Captured)>,
+}
+
+impl Processes {
+    fn new(program: &str, count: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            program: program.to_string(),
+            children: (0..count).map(|_| None).collect(),
+            captured: (0..count).map(|_| None).collect(),
+            tx,
+            rx,
+        }
+    }
+
+    /// Start the command for one host; its output is read in threads and sent back with
+    /// the host's index.
+    fn spawn(&mut self, host: &mut HostRun, i: usize) {
+        host.status = HostStatus::Running;
+        host.started = Some(Instant::now());
+        let child = Command::new(&self.program)
+            .args(&host.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                self.captured[i] = Some(Err(e));
+                return;
+            }
+        };
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        self.children[i] = Some(child);
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            // stderr 在另一个线程中读取，避免任一管道写满后卡住
+            let stderr = thread::spawn(move || read_all(stderr));
+            let stdout = read_all(stdout);
+            let stderr = stderr.join().unwrap_or_else(|_| Err(io::Error::other("reader panicked")));
+            // 接收端已关闭说明结果窗口已关闭，忽略即可
+            let _ = tx.send((i, stdout.and_then(|out| Ok((out, stderr?)))));
+        });
+    }
+
+    /// Hosts whose command has exited and whose output has been read completely.
+    fn poll(&mut self) -> Vec<(usize, io::Result<Output>)> {
+        while let Ok((i, captured)) = self.rx.try_recv() {
+            self.captured[i] = Some(captured);
+        }
+        let mut done = Vec::new();
+        for (i, slot) in self.captured.iter_mut().enumerate() {
+            if slot.is_none() {
+                continue;
+            }
+            // 输出读完后进程可能还没退出，等下一次 poll
+            let status = match self.children[i].as_mut().map(Child::try_wait) {
+                Some(Ok(None)) => continue,
+                Some(Ok(Some(status))) => Ok(status),
+                Some(Err(e)) => Err(e),
+                // 只有启动失败的主机没有进程，此时 captured 本身就是错误
+                None => Err(io::Error::other("the process was not started")),
+            };
+            self.children[i] = None;
+            let Some(captured) = slot.take() else { continue };
+            done.push((i, captured.and_then(|(stdout, stderr)| Ok(Output { status: status?, stdout, stderr }))));
+        }
+        done
+    }
+
+    /// Kill every command that is still running; they are reported by the next `poll`.
+    fn kill(&mut self) {
+        for child in self.children.iter_mut().flatten() {
+            let _ = child.kill();
+        }
+    }
+}
+
+impl Drop for Processes {
+    fn drop(&mut self) {
+        for child in self.children.iter_mut().flatten() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_all(pipe: Option<impl Read>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+fn record(program: &str, host: &mut HostRun, output: io::Result<Output>) {
+    host.duration = host.started.map(|at| at.elapsed());
+    match output {
+        Ok(output) => {
+            host.status = match output.status.code() {
+                Some(code) => HostStatus::Exited(code),
+                None => HostStatus::Killed,
+            };
+            host.stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            host.stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        }
+        Err(e) => host.status = HostStatus::Error(format!("failed to run {}: {}", program, e)),
+    }
+}
+
+/// Latest result of a watched host and how it compares with the round before.
+pub struct WatchHost {
+    pub run: HostRun,
+    pub previous: Option<(HostStatus, String)>, // 上一轮的状态和输出
+    pub changed: bool,                          // 最近一轮与上一轮不同
+    pub changed_at: Option<Instant>,
+    last_status: Option<HostStatus>, // 本轮开始前的状态，输出仍保存在 run 中
+}
+
+/// A command re-run on the same hosts every `interval`, like `watch`.
+pub struct Watch {
+    pub command: String,
+    pub hosts: Vec<WatchHost>,
+    pub interval: Duration,
+    pub round: usize,
+    pub paused: bool,
+    next_round: Instant,
+    processes: Processes,
+}
+
+impl Watch {
+    /// Start the first round; `targets` are (server name, program arguments).
+    pub fn start(program: &str, command: &str, targets: Vec<(String, Vec<String>)>, interval: Duration) -> Self {
+        let hosts: Vec<WatchHost> = targets
+            .into_iter()
+            .map(|(name, args)| WatchHost {
+                run: HostRun {
+                    name,
+                    args,
+                    status: HostStatus::Pending,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    started: None,
+                    duration: None,
+                },
+                previous: None,
+                changed: false,
+                changed_at: None,
+                last_status: None,
+            })
+            .collect();
+        let mut watch = Self {
+            command: command.to_string(),
+            processes: Processes::new(program, hosts.len()),
+            hosts,
+            interval,
+            round: 0,
+            paused: false,
+            next_round: Instant::now(),
+        };
+        watch.start_round();
+        watch
+    }
+
+    fn start_round(&mut self) {
+        for (i, host) in self.hosts.iter_mut().enumerate() {
+            if host.run.status.is_finished() {
+                host.last_status = Some(host.run.status.clone());
+            }
+            self.processes.spawn(&mut host.run, i);
+        }
+        self.round += 1;
+        self.next_round = Instant::now() + self.interval;
+    }
+
+    /// Collect finished hosts and start the next round when it is due.
+    pub fn poll(&mut self) {
+        for (i, output) in self.processes.poll() {
+            let host = &mut self.hosts[i];
+            let stdout = std::mem::take(&mut host.run.stdout);
+            record(&self.processes.program, &mut host.run, output);
+            if let Some(status) = host.last_status.take() {
+                host.changed = status != host.run.status || stdout != host.run.stdout;
+                host.previous = Some((status, stdout));
+                if host.changed {
+                    host.changed_at = Some(Instant::now());
+                }
+            }
+        }
+        // 上一轮还没结束时不开始新一轮，避免慢主机上进程堆积
+        if !self.paused && !self.round_running() && Instant::now() >= self.next_round {
+            self.start_round();
+        }
+    }
+
+    /// Start the next round as soon as the current one has finished.
+    pub fn run_now(&mut self) {
+        self.next_round = Instant::now();
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.next_round = self.next_round.checked_sub(self.interval).unwrap_or_else(Instant::now) + interval;
+        self.interval = interval;
+    }
+
+    pub fn round_running(&self) -> bool {
+        self.hosts.iter().any(|h| matches!(h.run.status, HostStatus::Running))
+    }
+
+    /// Time left until the next round, if it is waiting for one.
+    pub fn next_in(&self) -> Option<Duration> {
+        if self.paused || self.round_running() {
+            None
+        } else {
+            Some(self.next_round.saturating_duration_since(Instant::now()))
+        }
+    }
+
+    pub fn changed(&self) -> usize {
+        self.hosts.iter().filter(|h| h.changed).count()
+    }
+}
+
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Outputs larger than this (lines × lines) are shown as fully replaced instead of diffed.
+const DIFF_LIMIT: usize = 4_000_000;
+
+/// Line diff of two outputs, based on their longest common subsequence.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    if a.len() * b.len() > DIFF_LIMIT {
+        return a
+            .into_iter()
+            .map(DiffLine::Removed)
+            .chain(b.into_iter().map(DiffLine::Added))
+            .collect();
+    }
+
+    // lcs[i][j] 是 a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(a[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j]));
+            j += 1;
+        }
+    }
+    lines.extend(a[i..].iter().map(|l| DiffLine::Removed(l)));
+    lines.extend(b[j..].iter().map(|l| DiffLine::Added(l)));
+    lines
+}
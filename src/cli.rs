@@ -0,0 +1,84 @@
+use crate::pick::PickFormat;
+
+/// Options given on the command line.
+#[derive(Default)]
+pub struct Cli {
+    /// Print the commands sshx would run instead of running them.
+    pub dry_run: bool,
+    /// Replace sshx with the connection instead of returning to the TUI.
+    pub exec: bool,
+    /// Draw below the prompt instead of on the alternate screen.
+    pub inline: bool,
+    /// `sshx pick`: print the chosen server in this format instead of connecting.
+    pub pick: Option<PickFormat>,
+    /// `sshx open URI`: connect to the server without showing the TUI.
+    pub open: Option<String>,
+}
+
+pub const USAGE: &str = "\
+Usage: sshx [OPTIONS]
+       sshx pick [--format FORMAT]
+       sshx open [OPTIONS] ssh://[user@]host[:port]
+
+Options:
+  -n, --dry-run  Print connection and broadcast commands instead of running them
+  -x, --exec     Quit and run the chosen connection in place of sshx
+  -i, --inline   Draw a compact list below the prompt instead of using the whole screen
+  -h, --help     Print this help
+  -V, --version  Print the version
+
+Pick options:
+  -f, --format FORMAT  What to print for the server chosen with Enter: ssh (the command
+                       line, default), target (user@host), json, or a template such as
+                       '{user}@{host}:{port}'
+
+'sshx open' connects right away, with the settings of a matching saved server if the
+current profile has one; it can be registered as the handler for ssh:// links.";
+
+/// What `main` should do after parsing the arguments.
+pub enum Parsed {
+    Run(Cli),
+    Help,
+    Version,
+}
+
+impl Cli {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Parsed, String> {
+        let mut cli = Cli::default();
+        let mut pick = false;
+        let mut open = false;
+        let mut format = None;
+        let mut uri = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "pick" if !pick && !open => pick = true,
+                "open" if !pick && !open => open = true,
+                "-n" | "--dry-run" => cli.dry_run = true,
+                "-x" | "--exec" => cli.exec = true,
+                "-i" | "--inline" => cli.inline = true,
+                "-f" | "--format" => {
+                    format = Some(args.next().ok_or_else(|| format!("'{}' needs a value", arg))?);
+                }
+                "-h" | "--help" => return Ok(Parsed::Help),
+                "-V" | "--version" => return Ok(Parsed::Version),
+                _ => match arg.strip_prefix("--format=") {
+                    Some(value) => format = Some(value.to_string()),
+                    // 选项可以写在 URI 前后，第一个非选项参数就是 URI
+                    None if open && uri.is_none() && !arg.starts_with('-') => uri = Some(arg),
+                    None => return Err(format!("unexpected argument '{}'", arg)),
+                },
+            }
+        }
+        if open {
+            cli.open = Some(uri.ok_or("'open' needs an ssh:// URI")?);
+        }
+        match (pick, format) {
+            (true, format) => {
+                cli.pick = Some(PickFormat::parse(format.as_deref().unwrap_or("ssh"))?);
+            }
+            (false, Some(_)) => return Err("--format can only be used with 'sshx pick'".to_string()),
+            (false, None) => {}
+        }
+        Ok(Parsed::Run(cli))
+    }
+}
@@ -0,0 +1,30 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::{self, Write};
+
+use crate::tmux;
+
+/// Put `text` on the system clipboard with the OSC 52 escape sequence. The terminal does the
+/// copying, so this also works over ssh; terminals that do not support it ignore it.
+/// Inside tmux the sequence is sent the one way tmux lets through; the inner `Err` says how
+/// to configure tmux when neither is allowed.
+pub fn copy(out: &mut impl Write, text: &str, tmux: &str) -> io::Result<Result<(), String>> {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if !tmux::inside_tmux() {
+        out.write_all(sequence.as_bytes())?;
+        return out.flush().map(Ok);
+    }
+    // set-clipboard 为 on 时 tmux 自己处理 OSC 52；两种方式都发送会设置两次剪贴板
+    if tmux::show_option(tmux, "-sv", "set-clipboard").as_deref() == Some("on") {
+        out.write_all(sequence.as_bytes())?;
+        return out.flush().map(Ok);
+    }
+    // tmux 3.3 起 passthrough 默认关闭；更早的版本没有这个选项，总是放行
+    if tmux::show_option(tmux, "-Apv", "allow-passthrough").as_deref() == Some("off") {
+        return Ok(Err(
+            "tmux does not pass the clipboard on.\nRun `tmux set -g set-clipboard on` or add it to ~/.tmux.conf.".to_string(),
+        ));
+    }
+    let escaped = sequence.replace('\x1b', "\x1b\x1b");
+    write!(out, "\x1bPtmux;{}\x1b\\", escaped)?;
+    out.flush().map(Ok)
+}
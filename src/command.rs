@@ -6,44 +6,230 @@ use crossterm::{
 use ratatui::{backend::Backend, Terminal};
 use std::{
     io::{self, Write},
-    process::Command,
+    process::{Command, Stdio},
 };
 
+use crate::config::{Config, Launch, PauseAfterExit};
+use crate::tmux::{self, inside_tmux};
+
 pub fn run_external_command<B: Backend + std::io::Write>(
     terminal: &mut Terminal<B>,
+    config: &Config,
     program: &str,
     args: &[String],
 ) -> io::Result<()> {
-    // Drop TUI state
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-    terminal.show_cursor()?;
+    if config.dry_run {
+        return print_commands(terminal, config, &[(program.to_string(), args.to_vec())]);
+    }
+    suspend_tui(terminal, config)?;
 
     // Run command
     println!("Running {} {}...", program, args.join(" "));
     let status = Command::new(program)
         .args(args)
         .status();
+    report_exit(config, program, status)?;
+
+    resume_tui(terminal, config)
+}
+
+/// Run an interactive command where `config.launch` says. Outside the TUI it blocks like
+/// `run_external_command`; in tmux, screen or a terminal emulator sshx stays usable.
+/// The inner `Err` describes a launcher that could not be started. Tabs and exec are handled
+/// by the caller, which owns the TUI; here they suspend it like the other fallbacks.
+pub fn launch<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    config: &Config,
+    program: &str,
+    args: &[String],
+) -> io::Result<Result<(), String>> {
+    let programs = &config.programs;
+    let command = format_command(program, args);
+    let new_pane = |tmux_command: &str| {
+        let mut tmux_args = vec![tmux_command.to_string(), command.clone()];
+        // 连接立即失败时保留窗格，否则错误信息随窗格一起消失。与新窗格放在同一条命令中，
+        // 窗格退出前一定已设置；tmux 3.2 之前没有 "failed"，整条命令会被拒绝，所以按版本跳过
+        if tmux::version(&programs.tmux).is_some_and(|v| v >= (3, 2)) {
+            tmux_args.extend([";", "set-option", "-p", "remain-on-exit", "failed"].map(String::from));
+        }
+        Some((programs.tmux.clone(), tmux_args))
+    };
+    let launcher = match config.launch {
+        Launch::TmuxWindow if inside_tmux() => new_pane("new-window"),
+        Launch::TmuxSplit if inside_tmux() => new_pane("split-window"),
+        Launch::Screen if inside_screen() => {
+            let mut screen_args = vec!["-X".to_string(), "screen".to_string(), program.to_string()];
+            screen_args.extend_from_slice(args);
+            Some((programs.screen.clone(), screen_args))
+        }
+        Launch::Terminal => Some(terminal_command(&config.terminal, program, args)),
+        // 不在 tmux/screen 中时退回到挂起 TUI
+        _ => None,
+    };
+    let Some((launcher, launcher_args)) = launcher else {
+        return run_external_command(terminal, config, program, args).map(Ok);
+    };
+    if config.dry_run {
+        return print_commands(terminal, config, &[(launcher, launcher_args)]).map(Ok);
+    }
+    Ok(match config.launch {
+        Launch::Terminal => {
+            spawn_background(&launcher, &launcher_args).map_err(|e| format!("failed to run {}: {}", launcher, e))
+        }
+        _ => run_captured(&launcher, &launcher_args),
+    })
+}
+
+/// Replace sshx with `program`; only returns if it could not be started.
+#[cfg(unix)]
+pub fn exec(program: &str, args: &[String]) -> io::Error {
+    use std::os::unix::process::CommandExt;
+    Command::new(program).args(args).exec()
+}
+
+/// Windows cannot replace a process: run `program` and exit with its exit code.
+#[cfg(not(unix))]
+pub fn exec(program: &str, args: &[String]) -> io::Error {
+    match Command::new(program).args(args).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    }
+}
+
+/// True when sshx runs inside a GNU screen session.
+fn inside_screen() -> bool {
+    std::env::var_os("STY").is_some_and(|v| !v.is_empty())
+}
+
+/// Split the terminal template into words; a `{command}` word becomes the program and its
+/// arguments, and `{command}` inside a longer word becomes the quoted command line.
+fn terminal_command(template: &str, program: &str, args: &[String]) -> (String, Vec<String>) {
+    // 模板在加载配置时已检查过，这里不会失败
+    let words = shell_words::split(template).unwrap_or_default();
+    let mut expanded = Vec::new();
+    for word in words {
+        if word == "{command}" {
+            expanded.push(program.to_string());
+            expanded.extend_from_slice(args);
+        } else {
+            expanded.push(word.replace("{command}", &format_command(program, args)));
+        }
+    }
+    let launcher = expanded.remove(0);
+    (launcher, expanded)
+}
+
+/// Print commands instead of running them (`--dry-run`).
+pub fn print_commands<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    config: &Config,
+    commands: &[(String, Vec<String>)],
+) -> io::Result<()> {
+    suspend_tui(terminal, config)?;
+    for (program, args) in commands {
+        println!("{}", format_command(program, args));
+    }
+    println!("Dry run: nothing was executed. Press Enter to continue...");
+    wait_for_enter()?;
+    resume_tui(terminal, config)
+}
+
+/// Command line quoted the way a POSIX shell would need it.
+pub fn format_command(program: &str, args: &[String]) -> String {
+    shell_words::join(std::iter::once(program).chain(args.iter().map(String::as_str)))
+}
+
+/// Start a program without leaving the TUI; its output is discarded.
+pub fn spawn_background(program: &str, args: &[String]) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // 在后台线程等待子进程退出，避免留下僵尸进程
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Run a program without leaving the TUI; on failure the error holds what it printed to stderr.
+pub fn run_captured(program: &str, args: &[String]) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Leave the alternate screen so an external program can use the terminal. Inline, the
+/// list is erased and the program starts where it was.
+fn suspend_tui<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, config: &Config) -> io::Result<()> {
+    if config.inline {
+        terminal.clear()?;
+    }
+    disable_raw_mode()?;
+    if config.inline {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    } else {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    }
+    terminal.show_cursor()
+}
+
+fn resume_tui<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, config: &Config) -> io::Result<()> {
+    enable_raw_mode()?;
+    if config.inline {
+        anchor_inline(terminal)?;
+    } else {
+        execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    }
+    if config.mouse {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+    }
+    terminal.clear()
+}
 
-    match status {
+/// Move the inline viewport below whatever the external program printed.
+fn anchor_inline<B: Backend + std::io::Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    let (x, y) = terminal.get_cursor()?;
+    // ratatui 按记录的光标相对旧视口的偏移重新定位视口：
+    // 先把记录的位置设为旧视口顶部（偏移为 0），再直接把真实光标移回去
+    let top = terminal.get_frame().size().y;
+    terminal.set_cursor(0, top)?;
+    terminal.backend_mut().set_cursor(x, y)?;
+    if x > 0 {
+        terminal.backend_mut().append_lines(1)?;
+    }
+    let size = terminal.size()?;
+    terminal.resize(size)
+}
+
+/// Print how the program exited and pause according to `pause_after_exit`.
+fn report_exit(
+    config: &Config,
+    program: &str,
+    status: io::Result<std::process::ExitStatus>,
+) -> io::Result<()> {
+    let pause = match status {
+        Ok(s) if s.success() => config.pause_after_exit == PauseAfterExit::Always,
         Ok(s) => {
-            if !s.success() {
-                println!("Command exited with status: {}", s);
-                println!("Press Enter to continue...");
-                wait_for_enter()?;
-            }
+            println!("Command exited with status: {}", s);
+            config.pause_after_exit != PauseAfterExit::Never
         }
         Err(e) => {
             println!("Failed to execute {}: {}", program, e);
-            println!("Press Enter to continue...");
-            wait_for_enter()?;
+            config.pause_after_exit != PauseAfterExit::Never
         }
+    };
+    if pause {
+        println!("Press Enter to continue...");
+        wait_for_enter()?;
     }
-
-    // Restore TUI state
-    enable_raw_mode()?;
-    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
-    terminal.clear()?;
     Ok(())
 }
 
@@ -84,46 +270,31 @@ pub fn is_command_available(command: &str) -> bool {
 
 /// Windows-specific ssh-copy-id implementation
 #[cfg(target_os = "windows")]
-fn run_ssh_copy_id_windows(args: &[String]) -> io::Result<std::process::ExitStatus> {
+fn run_ssh_copy_id_windows(ssh: &str, args: &[String]) -> io::Result<std::process::ExitStatus> {
     use std::env;
     use std::path::PathBuf;
 
-    // Get the public key path
-    let userprofile = env::var("USERPROFILE")
-        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "USERPROFILE not set"))?;
-    let public_key = PathBuf::from(userprofile).join(".ssh").join("id_rsa.pub");
-
-    if !public_key.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("ERROR: failed to open ID file '{}': No such file", public_key.display())
-        ));
-    }
-
-    // Verify the public key exists and is readable
-    let _key_content = std::fs::read_to_string(&public_key)?;
-
     // Build the SSH command
     let mut ssh_args = Vec::new();
     let mut target = String::new();
+    let mut identity_file = None;
 
     // Parse args to extract target and options
     let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
-            "-p" => {
+            "-p" | "-o" => {
                 if i + 1 < args.len() {
-                    ssh_args.push("-p".to_string());
+                    ssh_args.push(args[i].clone());
                     ssh_args.push(args[i + 1].clone());
                     i += 2;
                 } else {
                     i += 1;
                 }
             }
-            "-o" => {
+            "-i" => {
                 if i + 1 < args.len() {
-                    ssh_args.push("-o".to_string());
-                    ssh_args.push(args[i + 1].clone());
+                    identity_file = Some(args[i + 1].clone());
                     i += 2;
                 } else {
                     i += 1;
@@ -136,18 +307,39 @@ fn run_ssh_copy_id_windows(args: &[String]) -> io::Result<std::process::ExitStat
         }
     }
 
+    // Get the public key path
+    let public_key = match identity_file {
+        Some(path) if path.ends_with(".pub") => PathBuf::from(path),
+        Some(path) => PathBuf::from(format!("{}.pub", path)),
+        None => {
+            let userprofile = env::var("USERPROFILE")
+                .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "USERPROFILE not set"))?;
+            PathBuf::from(userprofile).join(".ssh").join("id_rsa.pub")
+        }
+    };
+
+    if !public_key.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("ERROR: failed to open ID file '{}': No such file", public_key.display())
+        ));
+    }
+
+    // Verify the public key exists and is readable
+    let _key_content = std::fs::read_to_string(&public_key)?;
+
     ssh_args.push(target);
     ssh_args.push("umask 077; test -d .ssh || mkdir .ssh ; cat >> .ssh/authorized_keys || exit 1".to_string());
 
     // Execute: type public_key | ssh args
     let output = if cfg!(target_os = "windows") {
         Command::new("cmd")
-            .args(&["/C", &format!("type \"{}\" | ssh {}", public_key.display(), ssh_args.join(" "))])
+            .args(&["/C", &format!("type \"{}\" | {} {}", public_key.display(), ssh, ssh_args.join(" "))])
             .status()
     } else {
         Command::new("sh")
             .arg("-c")
-            .arg(format!("cat '{}' | ssh {}", public_key.display(), ssh_args.join(" ")))
+            .arg(format!("cat '{}' | {} {}", public_key.display(), ssh, ssh_args.join(" ")))
             .status()
     };
 
@@ -157,60 +349,28 @@ fn run_ssh_copy_id_windows(args: &[String]) -> io::Result<std::process::ExitStat
 /// Run ssh-copy-id with platform-specific implementation
 pub fn run_ssh_copy_id<B: Backend + std::io::Write>(
     terminal: &mut Terminal<B>,
+    config: &Config,
     args: &[String],
 ) -> io::Result<()> {
-    // Drop TUI state
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-    terminal.show_cursor()?;
+    if config.dry_run {
+        return print_commands(terminal, config, &[(config.programs.ssh_copy_id.clone(), args.to_vec())]);
+    }
+    suspend_tui(terminal, config)?;
 
     #[cfg(target_os = "windows")]
-    {
+    let status = {
         println!("Running ssh-copy-id (Windows implementation)...");
-        let status = run_ssh_copy_id_windows(args);
-
-        match status {
-            Ok(s) => {
-                if !s.success() {
-                    println!("Command exited with status: {}", s);
-                    println!("Press Enter to continue...");
-                    wait_for_enter()?;
-                }
-            }
-            Err(e) => {
-                println!("Failed to execute ssh-copy-id: {}", e);
-                println!("Press Enter to continue...");
-                wait_for_enter()?;
-            }
-        }
-    }
+        run_ssh_copy_id_windows(&config.programs.ssh, args)
+    };
 
     #[cfg(not(target_os = "windows"))]
-    {
-        println!("Running ssh-copy-id {}...", args.join(" "));
-        let status = Command::new("ssh-copy-id")
+    let status = {
+        println!("Running {} {}...", config.programs.ssh_copy_id, args.join(" "));
+        Command::new(&config.programs.ssh_copy_id)
             .args(args)
-            .status();
-
-        match status {
-            Ok(s) => {
-                if !s.success() {
-                    println!("Command exited with status: {}", s);
-                    println!("Press Enter to continue...");
-                    wait_for_enter()?;
-                }
-            }
-            Err(e) => {
-                println!("Failed to execute ssh-copy-id: {}", e);
-                println!("Press Enter to continue...");
-                wait_for_enter()?;
-            }
-        }
-    }
+            .status()
+    };
 
-    // Restore TUI state
-    enable_raw_mode()?;
-    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
-    terminal.clear()?;
-    Ok(())
-}
\ No newline at end of file
+    report_exit(config, "ssh-copy-id", status)?;
+    resume_tui(terminal, config)
+}
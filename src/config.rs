@@ -0,0 +1,338 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::server::{DEFAULT_PORT, DEFAULT_USER};
+use crate::template;
+use crate::theme::ThemeSpec;
+
+/// Contents of `config.toml` in the sshx config directory.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Protocol used by the `connect` action (Enter).
+    pub default_protocol: Protocol,
+    /// Ask before quitting sshx.
+    pub confirm_quit: bool,
+    /// Move the last connected server to the top of the list.
+    pub reorder_on_connect: bool,
+    /// When to wait for Enter after an external command exits.
+    pub pause_after_exit: PauseAfterExit,
+    /// Where interactive connections run.
+    pub launch: Launch,
+    /// Terminal emulator command for `launch = "terminal"`; `{command}` is the connection.
+    pub terminal: String,
+    /// Draw a few lines below the prompt instead of taking over the whole screen.
+    pub inline: bool,
+    /// Lines used by the inline mode.
+    pub inline_height: u16,
+    /// Capture mouse events; disable to keep the terminal's own text selection.
+    pub mouse: bool,
+    /// Always show the exact commands and ask for confirmation before a broadcast runs.
+    pub broadcast_preview: bool,
+    pub broadcast: BroadcastOptions,
+    pub programs: Programs,
+    pub form: FormDefaults,
+    /// Action name -> key binding(s), e.g. `ssh = "l"` or `first = ["g g", "Home"]`.
+    #[serde(deserialize_with = "deserialize_bindings")]
+    pub keys: HashMap<String, Vec<String>>,
+    /// Name of a built-in theme or of one defined under `[themes]`.
+    pub theme: Option<String>,
+    pub themes: HashMap<String, ThemeSpec>,
+    /// Extra server-list actions from `[[actions]]` entries.
+    pub actions: Vec<CustomAction>,
+    /// Set by `--dry-run`: print commands instead of running them.
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_protocol: Protocol::Ssh,
+            confirm_quit: false,
+            reorder_on_connect: true,
+            pause_after_exit: PauseAfterExit::OnError,
+            launch: Launch::Suspend,
+            terminal: String::new(),
+            inline: false,
+            inline_height: 12,
+            mouse: true,
+            broadcast_preview: false,
+            broadcast: BroadcastOptions::default(),
+            programs: Programs::default(),
+            form: FormDefaults::default(),
+            keys: HashMap::new(),
+            theme: None,
+            themes: HashMap::new(),
+            actions: Vec::new(),
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Ssh,
+    Sftp,
+    Mosh,
+}
+
+/// Where `ssh`, `sftp`, `mosh` and other interactive commands run.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Launch {
+    /// Leave the TUI until the command exits.
+    Suspend,
+    /// A new tmux window; needs sshx to run inside tmux.
+    TmuxWindow,
+    /// A new pane next to sshx; needs sshx to run inside tmux.
+    TmuxSplit,
+    /// A new GNU screen window; needs sshx to run inside screen.
+    Screen,
+    /// A new terminal emulator window started from `terminal`.
+    Terminal,
+    /// A tab inside sshx.
+    Tab,
+    /// Quit sshx and replace it with the command, as if it had been run directly.
+    Exec,
+}
+
+impl Launch {
+    /// What a connection opens in, for messages.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Launch::Suspend => "session",
+            Launch::TmuxWindow => "tmux window",
+            Launch::TmuxSplit => "tmux pane",
+            Launch::Screen => "screen window",
+            Launch::Terminal => "terminal window",
+            Launch::Tab => "tab",
+            Launch::Exec => "process",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum PauseAfterExit {
+    Always,
+    OnError,
+    Never,
+}
+
+/// Default rollout for broadcasts; each can be changed before a broadcast starts.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct BroadcastOptions {
+    /// Hosts run at the same time; 0 runs every target at once.
+    pub batch_size: usize,
+    /// Seconds to wait between batches.
+    pub pause: u64,
+    pub on_failure: OnFailure,
+    /// Seconds between rounds in watch mode.
+    pub interval: u64,
+}
+
+impl Default for BroadcastOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 0,
+            pause: 0,
+            on_failure: OnFailure::Ask,
+            interval: 2,
+        }
+    }
+}
+
+/// What a rolling broadcast does after a batch in which a host failed.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    Stop,
+    Ask,
+    Continue,
+}
+
+impl OnFailure {
+    pub fn name(self) -> &'static str {
+        match self {
+            OnFailure::Stop => "stop",
+            OnFailure::Ask => "ask",
+            OnFailure::Continue => "continue",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            OnFailure::Stop => OnFailure::Ask,
+            OnFailure::Ask => OnFailure::Continue,
+            OnFailure::Continue => OnFailure::Stop,
+        }
+    }
+}
+
+/// Programs used to connect; either names looked up on PATH or full paths.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Programs {
+    pub ssh: String,
+    pub sftp: String,
+    pub mosh: String,
+    pub ssh_copy_id: String,
+    pub autossh: String,
+    pub et: String,
+    pub telnet: String,
+    pub picocom: String,
+    pub docker: String,
+    pub kubectl: String,
+    pub tmux: String,
+    pub screen: String,
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Self {
+            ssh: "ssh".to_string(),
+            sftp: "sftp".to_string(),
+            mosh: "mosh".to_string(),
+            ssh_copy_id: "ssh-copy-id".to_string(),
+            autossh: "autossh".to_string(),
+            et: "et".to_string(),
+            telnet: "telnet".to_string(),
+            picocom: "picocom".to_string(),
+            docker: "docker".to_string(),
+            kubectl: "kubectl".to_string(),
+            tmux: "tmux".to_string(),
+            screen: "screen".to_string(),
+        }
+    }
+}
+
+/// A user-defined action run against the selected server.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomAction {
+    /// Key binding, written like the ones in `[keys]`.
+    pub key: String,
+    pub label: String,
+    /// Command template; `{name}`, `{user}`, `{host}`, `{port}` and `{jump}` are replaced.
+    pub command: String,
+    /// Start the command without leaving the TUI and without waiting for it.
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// Fallback values for servers whose profile does not set them either.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormDefaults {
+    pub user: String,
+    pub port: u16,
+}
+
+impl Default for FormDefaults {
+    fn default() -> Self {
+        Self {
+            user: DEFAULT_USER.to_string(),
+            port: DEFAULT_PORT,
+        }
+    }
+}
+
+fn deserialize_bindings<'de, D>(deserializer: D) -> Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let raw = HashMap::<String, OneOrMany>::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .map(|(action, keys)| match keys {
+            OneOrMany::One(key) => (action, vec![key]),
+            OneOrMany::Many(keys) => (action, keys),
+        })
+        .collect())
+}
+
+impl Config {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join("config.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let config: Self =
+            toml::from_str(&data).with_context(|| format!("invalid {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("invalid {}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let programs = [
+            ("ssh", &self.programs.ssh),
+            ("sftp", &self.programs.sftp),
+            ("mosh", &self.programs.mosh),
+            ("ssh_copy_id", &self.programs.ssh_copy_id),
+            ("autossh", &self.programs.autossh),
+            ("et", &self.programs.et),
+            ("telnet", &self.programs.telnet),
+            ("picocom", &self.programs.picocom),
+            ("docker", &self.programs.docker),
+            ("kubectl", &self.programs.kubectl),
+            ("tmux", &self.programs.tmux),
+            ("screen", &self.programs.screen),
+        ];
+        for (key, program) in programs {
+            if program.trim().is_empty() {
+                bail!("programs.{} must not be empty", key);
+            }
+            // 只检查显式路径；裸命令名在使用时才查找
+            let path = Path::new(program);
+            if path.components().count() > 1 && !path.is_file() {
+                bail!("programs.{}: '{}' does not exist", key, program);
+            }
+        }
+        if self.form.port == 0 {
+            bail!("form.port must be between 1 and 65535");
+        }
+        if self.form.user.is_empty() || self.form.user.contains(|c: char| c.is_whitespace() || c == '@') {
+            bail!("form.user must be a non-empty user name without spaces or '@'");
+        }
+        if self.launch == Launch::Terminal && !self.terminal.contains("{command}") {
+            bail!("terminal must contain {{command}} when launch = \"terminal\", e.g. \"alacritty -e {{command}}\"");
+        }
+        if let Err(e) = shell_words::split(&self.terminal) {
+            bail!("terminal: {}", e);
+        }
+        if self.inline_height < 5 {
+            bail!("inline_height must be at least 5 lines");
+        }
+        if self.broadcast.interval == 0 {
+            bail!("broadcast.interval must be at least 1 second");
+        }
+        for action in &self.actions {
+            if action.label.trim().is_empty() {
+                bail!("actions: every action needs a label");
+            }
+            if action.command.trim().is_empty() {
+                bail!("actions.{}: command must not be empty", action.label);
+            }
+            // 占位符可能是服务器自定义变量，这里只检查语法
+            if let Err(e) = template::check(&action.command) {
+                bail!("actions.{}: {}", action.label, e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::FormField;
+use crate::config::Programs;
+use crate::server::{ProfileDefaults, Server};
+
+/// How sshx connects to a server.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Driver {
+    #[default]
+    Ssh,
+    Autossh,
+    Et,
+    Telnet,
+    Serial,
+    Docker,
+    Kubectl,
+    Custom,
+}
+
+impl Driver {
+    pub const ALL: [Driver; 8] = [
+        Driver::Ssh,
+        Driver::Autossh,
+        Driver::Et,
+        Driver::Telnet,
+        Driver::Serial,
+        Driver::Docker,
+        Driver::Kubectl,
+        Driver::Custom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Driver::Ssh => "ssh",
+            Driver::Autossh => "autossh",
+            Driver::Et => "et",
+            Driver::Telnet => "telnet",
+            Driver::Serial => "serial",
+            Driver::Docker => "docker",
+            Driver::Kubectl => "kubectl",
+            Driver::Custom => "custom",
+        }
+    }
+
+    /// Drivers that talk plain ssh, so sftp, mosh, ssh-copy-id and broadcast work too.
+    pub fn is_ssh(self) -> bool {
+        matches!(self, Driver::Ssh | Driver::Autossh)
+    }
+
+    pub fn default_port(self) -> Option<u16> {
+        match self {
+            Driver::Ssh | Driver::Autossh | Driver::Custom => Some(22),
+            Driver::Et => Some(2022),
+            Driver::Telnet => Some(23),
+            Driver::Serial | Driver::Docker | Driver::Kubectl => None,
+        }
+    }
+
+    /// Form fields the driver uses, after Name and Driver; every driver ends with Vars.
+    pub fn fields(self) -> &'static [FormField] {
+        match self {
+            Driver::Ssh | Driver::Autossh => &[
+                FormField::User,
+                FormField::Host,
+                FormField::Port,
+                FormField::JumpHost,
+                FormField::IdentityFile,
+                FormField::SshOptions,
+                FormField::Vars,
+            ],
+            Driver::Et => &[
+                FormField::User,
+                FormField::Host,
+                FormField::Port,
+                FormField::JumpHost,
+                FormField::Vars,
+            ],
+            Driver::Telnet => &[FormField::User, FormField::Host, FormField::Port, FormField::Vars],
+            Driver::Serial => &[FormField::Target, FormField::Baud, FormField::Vars],
+            Driver::Docker => &[FormField::Target, FormField::User, FormField::Command, FormField::Vars],
+            Driver::Kubectl => &[
+                FormField::Target,
+                FormField::Namespace,
+                FormField::Command,
+                FormField::Vars,
+            ],
+            Driver::Custom => &[
+                FormField::User,
+                FormField::Host,
+                FormField::Port,
+                FormField::JumpHost,
+                FormField::Command,
+                FormField::Vars,
+            ],
+        }
+    }
+
+    /// Fields that must not be left empty.
+    pub fn required(self) -> &'static [FormField] {
+        match self {
+            Driver::Ssh | Driver::Autossh | Driver::Et | Driver::Telnet => &[FormField::Host],
+            Driver::Serial | Driver::Docker | Driver::Kubectl => &[FormField::Target],
+            Driver::Custom => &[FormField::Command],
+        }
+    }
+
+    pub fn next(self) -> Driver {
+        let idx = Driver::ALL.iter().position(|d| *d == self).unwrap_or(0);
+        Driver::ALL[(idx + 1) % Driver::ALL.len()]
+    }
+
+    pub fn previous(self) -> Driver {
+        let idx = Driver::ALL.iter().position(|d| *d == self).unwrap_or(0);
+        Driver::ALL[(idx + Driver::ALL.len() - 1) % Driver::ALL.len()]
+    }
+
+    /// Program and arguments used to open an interactive session.
+    pub fn command(
+        self,
+        server: &Server,
+        defaults: &ProfileDefaults,
+        programs: &Programs,
+    ) -> Result<(String, Vec<String>), String> {
+        Ok(match self {
+            Driver::Ssh => (programs.ssh.clone(), server.to_ssh_args(defaults)),
+            Driver::Autossh => (programs.autossh.clone(), server.to_autossh_args(defaults)),
+            Driver::Et => (programs.et.clone(), server.to_et_args(defaults)?),
+            Driver::Telnet => (programs.telnet.clone(), server.to_telnet_args(defaults)),
+            Driver::Serial => (programs.picocom.clone(), server.to_picocom_args()),
+            Driver::Docker => (programs.docker.clone(), server.to_docker_args()?),
+            Driver::Kubectl => (programs.kubectl.clone(), server.to_kubectl_args()?),
+            Driver::Custom => {
+                let mut words = server.to_custom_args(defaults)?;
+                if words.is_empty() {
+                    return Err("the command template is empty".to_string());
+                }
+                let program = words.remove(0);
+                (program, words)
+            }
+        })
+    }
+}
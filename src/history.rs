@@ -0,0 +1,141 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Number of broadcast commands kept in `history.json`.
+const HISTORY_LIMIT: usize = 500;
+
+/// Broadcast commands that were run, oldest first.
+pub struct History {
+    entries: Vec<String>,
+    path: PathBuf,
+    load_error: Option<String>, // 文件无法读取时不再写入，避免覆盖
+}
+
+impl History {
+    /// Load `history.json` from the data directory; a missing file is empty. A file that
+    /// cannot be read is also empty, but is not overwritten; see `load_error`.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join("history.json");
+        let (entries, load_error) = load_json(&path);
+        Self { entries, path, load_error }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Why the file could not be loaded; until it is fixed nothing is saved to it.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Record a command as the most recent entry, dropping older duplicates.
+    pub fn push(&mut self, command: &str) -> Result<()> {
+        if let Some(error) = &self.load_error {
+            anyhow::bail!("{}", error);
+        }
+        self.entries.retain(|c| c != command);
+        self.entries.push(command.to_string());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.drain(..self.entries.len() - HISTORY_LIMIT);
+        }
+        write_json(&self.path, &self.entries)
+    }
+
+    /// Index of the newest entry before `before` that contains `query`.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|c| c.contains(query))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snippet {
+    pub name: String,
+    pub command: String,
+}
+
+/// Named broadcast commands saved by the user, sorted by name.
+pub struct Snippets {
+    items: Vec<Snippet>,
+    path: PathBuf,
+    load_error: Option<String>,
+}
+
+impl Snippets {
+    /// Load `snippets.json` from the data directory, like `History::load`.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join("snippets.json");
+        let (items, load_error) = load_json(&path);
+        Self { items, path, load_error }
+    }
+
+    pub fn items(&self) -> &[Snippet] {
+        &self.items
+    }
+
+    /// Why the file could not be loaded; until it is fixed nothing is saved to it.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Add a snippet, replacing any existing one with the same name.
+    pub fn save(&mut self, name: &str, command: &str) -> Result<()> {
+        if let Some(error) = &self.load_error {
+            anyhow::bail!("{}", error);
+        }
+        match self.items.iter_mut().find(|s| s.name == name) {
+            Some(snippet) => snippet.command = command.to_string(),
+            None => {
+                self.items.push(Snippet {
+                    name: name.to_string(),
+                    command: command.to_string(),
+                });
+                self.items.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+        }
+        write_json(&self.path, &self.items)
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Result<()> {
+        if let Some(error) = &self.load_error {
+            anyhow::bail!("{}", error);
+        }
+        if idx < self.items.len() {
+            self.items.remove(idx);
+        }
+        write_json(&self.path, &self.items)
+    }
+}
+
+/// The file's contents, or empty if it does not exist. Any other failure is returned
+/// with an empty value so the caller can keep running without overwriting the file.
+fn load_json<T: for<'de> Deserialize<'de> + Default>(path: &Path) -> (T, Option<String>) {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (T::default(), None),
+        Err(e) => return (T::default(), Some(format!("Could not read {}: {}", path.display(), e))),
+    };
+    match serde_json::from_str(&data) {
+        Ok(value) => (value, None),
+        Err(e) => (T::default(), Some(format!("Could not load {}: {}", path.display(), e))),
+    }
+}
+
+pub fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+    let data = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
@@ -0,0 +1,484 @@
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+use crate::config::CustomAction;
+
+/// Actions available from the server list.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Quit,
+    Next,
+    Previous,
+    SelectFirst,
+    SelectLast,
+    Connect,
+    ConnectSsh,
+    ConnectSftp,
+    ConnectMosh,
+    CopyId,
+    CopyCommand,
+    CopyTarget,
+    CopyHost,
+    Broadcast,
+    RunHistory,
+    Tail,
+    Tmux,
+    OpenTab,
+    Tabs,
+    QuickConnect,
+    Share,
+    Import,
+    NewServer,
+    EditServer,
+    DeleteServer,
+    SelectProfile,
+    EditDefaults,
+    Help,
+    Custom(usize), // config.actions 中的索引
+}
+
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::Connect,
+        Action::ConnectSsh,
+        Action::ConnectSftp,
+        Action::ConnectMosh,
+        Action::Broadcast,
+        Action::RunHistory,
+        Action::Tail,
+        Action::Tmux,
+        Action::OpenTab,
+        Action::Tabs,
+        Action::QuickConnect,
+        Action::NewServer,
+        Action::Share,
+        Action::Import,
+        Action::SelectProfile,
+        Action::EditDefaults,
+        Action::CopyId,
+        Action::CopyCommand,
+        Action::CopyTarget,
+        Action::CopyHost,
+        Action::EditServer,
+        Action::DeleteServer,
+        Action::Help,
+        Action::Quit,
+        Action::Next,
+        Action::Previous,
+        Action::SelectFirst,
+        Action::SelectLast,
+    ];
+
+    /// Name used for the action in `config.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::SelectFirst => "first",
+            Action::SelectLast => "last",
+            Action::Connect => "connect",
+            Action::ConnectSsh => "ssh",
+            Action::ConnectSftp => "sftp",
+            Action::ConnectMosh => "mosh",
+            Action::CopyId => "copy_id",
+            Action::CopyCommand => "copy_command",
+            Action::CopyTarget => "copy_target",
+            Action::CopyHost => "copy_host",
+            Action::Broadcast => "broadcast",
+            Action::RunHistory => "runs",
+            Action::Tail => "tail",
+            Action::Tmux => "tmux",
+            Action::OpenTab => "tab",
+            Action::Tabs => "tabs",
+            Action::QuickConnect => "quick",
+            Action::Share => "share",
+            Action::Import => "import",
+            Action::NewServer => "new",
+            Action::EditServer => "edit",
+            Action::DeleteServer => "delete",
+            Action::SelectProfile => "profiles",
+            Action::EditDefaults => "defaults",
+            Action::Help => "help",
+            Action::Custom(_) => "custom",
+        }
+    }
+
+    /// Short label shown in the help bar.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Next => "Down",
+            Action::Previous => "Up",
+            Action::SelectFirst => "Top",
+            Action::SelectLast => "Bottom",
+            Action::Connect => "Connect",
+            Action::ConnectSsh => "SSH",
+            Action::ConnectSftp => "SFTP",
+            Action::ConnectMosh => "Mosh",
+            Action::CopyId => "Copy ID",
+            Action::CopyCommand => "Copy Cmd",
+            Action::CopyTarget => "Copy Target",
+            Action::CopyHost => "Copy Host",
+            Action::Broadcast => "Broadcast",
+            Action::RunHistory => "Runs",
+            Action::Tail => "Tail",
+            Action::Tmux => "tmux",
+            Action::OpenTab => "Tab",
+            Action::Tabs => "Tabs",
+            Action::QuickConnect => "Quick",
+            Action::Share => "Share",
+            Action::Import => "Import",
+            Action::NewServer => "New",
+            Action::EditServer => "Edit",
+            Action::DeleteServer => "Delete",
+            Action::SelectProfile => "Profile",
+            Action::EditDefaults => "Defaults",
+            Action::Help => "Help",
+            Action::Custom(_) => "Custom",
+        }
+    }
+
+    /// Longer description shown in the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit sshx",
+            Action::Next => "Select next server",
+            Action::Previous => "Select previous server",
+            Action::SelectFirst => "Jump to first server",
+            Action::SelectLast => "Jump to last server",
+            Action::Connect => "Connect via the default protocol",
+            Action::ConnectSsh => "Connect via ssh",
+            Action::ConnectSftp => "Connect via sftp",
+            Action::ConnectMosh => "Connect via mosh",
+            Action::CopyId => "Copy SSH public key (ssh-copy-id)",
+            Action::CopyCommand => "Copy the ssh command line to the clipboard",
+            Action::CopyTarget => "Copy user@host to the clipboard",
+            Action::CopyHost => "Copy the host to the clipboard",
+            Action::Broadcast => "Broadcast command to multiple servers",
+            Action::RunHistory => "Browse past broadcast runs",
+            Action::Tail => "Follow logs on multiple servers",
+            Action::Tmux => "Open servers in tmux panes with synchronized input",
+            Action::OpenTab => "Open an ssh session in a tab inside sshx",
+            Action::Tabs => "Tab prefix: then 0 list, 1-9 tab, n/p next/previous, x close",
+            Action::QuickConnect => "Connect to a host that is not saved",
+            Action::Share => "Show the selected server as an ssh:// URI, JSON or TOML",
+            Action::Import => "Add servers from a pasted URI, JSON or TOML snippet",
+            Action::NewServer => "Add a new server",
+            Action::EditServer => "Edit the selected server",
+            Action::DeleteServer => "Delete the selected server",
+            Action::SelectProfile => "Open profile selection menu",
+            Action::EditDefaults => "Edit the current profile's defaults",
+            Action::Help => "Show this help",
+            Action::Custom(_) => "Custom action",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "Esc", "Ctrl+c", "Ctrl+d"],
+            Action::Next => &["j", "Down", "Tab"],
+            Action::Previous => &["k", "Up", "BackTab"],
+            Action::SelectFirst => &["g g"],
+            Action::SelectLast => &["G"],
+            Action::Connect => &["Enter"],
+            Action::ConnectSsh => &["S"],
+            Action::ConnectSftp => &["s"],
+            Action::ConnectMosh => &["m"],
+            Action::CopyId => &["c"],
+            Action::CopyCommand => &["y y"],
+            Action::CopyTarget => &["y u"],
+            Action::CopyHost => &["y h"],
+            Action::Broadcast => &["p"],
+            Action::RunHistory => &["r"],
+            Action::Tail => &["t"],
+            Action::Tmux => &["T"],
+            Action::OpenTab => &["o"],
+            Action::Tabs => &["Ctrl+a"],
+            Action::QuickConnect => &[":"],
+            Action::Share => &["x"],
+            Action::Import => &["I"],
+            Action::NewServer => &["n"],
+            Action::EditServer => &["i"],
+            Action::DeleteServer => &["d"],
+            Action::SelectProfile => &["e"],
+            Action::EditDefaults => &["D"],
+            Action::Help => &["?"],
+            Action::Custom(_) => &[],
+        }
+    }
+}
+
+/// A single key press with its modifiers.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already part of the character (or of BackTab)
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+
+    fn parse(spec: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        loop {
+            let lower = rest.to_ascii_lowercase();
+            if lower.starts_with("ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+            } else if lower.starts_with("alt+") {
+                modifiers |= KeyModifiers::ALT;
+            } else if lower.starts_with("shift+") {
+                modifiers |= KeyModifiers::SHIFT;
+            } else {
+                break;
+            }
+            rest = &rest[rest.find('+').unwrap() + 1..];
+        }
+
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            name if name.len() > 1 && name.starts_with('f') => name[1..]
+                .parse()
+                .ok()
+                .filter(|n| (1..=12).contains(n))
+                .map(KeyCode::F)
+                .ok_or_else(|| anyhow!("unknown key '{}'", spec))?,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    }
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!("unknown key '{}'", spec),
+                }
+            }
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Result of looking up the keys pressed so far.
+pub enum KeyMatch {
+    Action(Action),
+    Pending, // 已按下的键是某个多键序列的前缀
+    None,
+}
+
+/// Key bindings for the server list, possibly remapped by the user.
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyChord>, Action)>,
+    custom_labels: Vec<String>,
+}
+
+impl Keymap {
+    /// Build the keymap from the defaults plus `[keys]` overrides and `[[actions]]`.
+    /// Overriding an action replaces all of its default bindings.
+    pub fn new(overrides: &HashMap<String, Vec<String>>, custom: &[CustomAction]) -> Result<Self> {
+        for name in overrides.keys() {
+            if Action::from_name(name).is_none() {
+                bail!("[keys] unknown action '{}'", name);
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for action in Action::ALL {
+            let specs: Vec<&str> = match overrides.get(action.name()) {
+                Some(specs) => specs.iter().map(String::as_str).collect(),
+                None => action.default_keys().to_vec(),
+            };
+            for spec in specs {
+                let sequence = parse_sequence(spec)
+                    .map_err(|e| anyhow!("[keys] {}: {}", action.name(), e))?;
+                // 标签页中按键直接送给会话，前缀无法等待后续按键
+                if action == Action::Tabs && sequence.len() > 1 {
+                    bail!("[keys] tabs: '{}' must be a single key", spec);
+                }
+                bindings.push((sequence, action));
+            }
+        }
+        for (i, action) in custom.iter().enumerate() {
+            let sequence = parse_sequence(&action.key)
+                .map_err(|e| anyhow!("[[actions]] {}: {}", action.label, e))?;
+            bindings.push((sequence, Action::Custom(i)));
+        }
+        let keymap = Self {
+            bindings,
+            custom_labels: custom.iter().map(|a| a.label.clone()).collect(),
+        };
+
+        // A binding that is a prefix of another could never be completed
+        for (i, (a, action_a)) in keymap.bindings.iter().enumerate() {
+            for (b, action_b) in keymap.bindings.iter().skip(i + 1) {
+                let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+                if long.starts_with(short) {
+                    bail!(
+                        "[keys] '{}' ({}) conflicts with '{}' ({})",
+                        format_sequence(a),
+                        keymap.name(*action_a),
+                        format_sequence(b),
+                        keymap.name(*action_b)
+                    );
+                }
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Built-in actions followed by the custom ones.
+    pub fn actions(&self) -> Vec<Action> {
+        Action::ALL
+            .into_iter()
+            .chain((0..self.custom_labels.len()).map(Action::Custom))
+            .collect()
+    }
+
+    fn name(&self, action: Action) -> &str {
+        match action {
+            Action::Custom(i) => &self.custom_labels[i],
+            action => action.name(),
+        }
+    }
+
+    /// Help bar label; custom actions use the label from their config entry.
+    pub fn label(&self, action: Action) -> &str {
+        match action {
+            Action::Custom(i) => &self.custom_labels[i],
+            action => action.label(),
+        }
+    }
+
+    pub fn description(&self, action: Action) -> &str {
+        match action {
+            Action::Custom(i) => &self.custom_labels[i],
+            action => action.description(),
+        }
+    }
+
+    pub fn resolve(&self, pressed: &[KeyChord]) -> KeyMatch {
+        let mut pending = false;
+        for (sequence, action) in &self.bindings {
+            if sequence.as_slice() == pressed {
+                return KeyMatch::Action(*action);
+            }
+            if sequence.starts_with(pressed) {
+                pending = true;
+            }
+        }
+        if pending {
+            KeyMatch::Pending
+        } else {
+            KeyMatch::None
+        }
+    }
+
+    /// True when `key` alone is bound to `action`.
+    pub fn is_key(&self, action: Action, key: KeyEvent) -> bool {
+        let chord = KeyChord::from(key);
+        self.bindings.iter().any(|(sequence, a)| *a == action && sequence.as_slice() == [chord])
+    }
+
+    /// All key sequences bound to an action, formatted for display.
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(sequence, _)| format_sequence(sequence))
+            .collect()
+    }
+
+    /// Help bar text for the server list.
+    pub fn help_text(&self) -> String {
+        self.actions()
+            .into_iter()
+            .filter(|a| !matches!(a, Action::Next | Action::Previous | Action::SelectFirst | Action::SelectLast))
+            .filter_map(|a| {
+                self.keys_for(a)
+                    .into_iter()
+                    .next()
+                    .map(|key| format!("{}: {}", key, self.label(a)))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+fn parse_sequence(spec: &str) -> Result<Vec<KeyChord>> {
+    let sequence = spec
+        .split_whitespace()
+        .map(KeyChord::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if sequence.is_empty() {
+        bail!("empty key binding");
+    }
+    Ok(sequence)
+}
+
+fn format_sequence(sequence: &[KeyChord]) -> String {
+    let keys: Vec<String> = sequence.iter().map(|k| k.to_string()).collect();
+    // 单字符组成的序列（如 gg）直接拼接显示
+    if keys.len() > 1 && keys.iter().all(|k| k.chars().count() == 1) {
+        keys.concat()
+    } else {
+        keys.join(" ")
+    }
+}
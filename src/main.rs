@@ -2,395 +2,1499 @@ mod app;
 mod server;
 mod ui;
 mod command;
+mod config;
+mod keymap;
+mod theme;
+mod validate;
+mod driver;
+mod template;
+mod history;
+mod broadcast;
+mod report;
+mod tail;
+mod tmux;
+mod session;
+mod cli;
+mod pick;
+mod share;
+mod clipboard;
 
-use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    Terminal,
+    Terminal, TerminalOptions, Viewport,
 };
 use std::{
     error::Error,
     io,
+    time::{Duration, Instant, SystemTime},
 };
 
-use app::{App, InputMode, AddingState, EditingState, BroadcastState, BroadcastPhase};
-use command::{run_external_command, run_ssh_copy_id, is_command_available};
-use ui::ui;
+use cli::{Cli, Parsed};
+use app::{App, ClickAreas, InputMode, ConnectionForm, FormField, FormKind, BroadcastState, BroadcastPhase, ResultsView, TailPhase, TailSetup, TailView, TargetSelection, WatchView};
+use broadcast::{Rollout, RolloutState, Watch};
+use report::{file_stamp, format_timestamp, unix_secs, ReportFormat};
+use tail::{Tail, TailSource};
+use command::{exec, launch, run_external_command, run_ssh_copy_id, is_command_available, spawn_background, run_captured, print_commands, format_command};
+use config::{BroadcastOptions, Launch, Protocol};
+use driver::Driver;
 use server::Server;
+use share::ShareFormat;
+use session::{Session, SessionStatus};
+use keymap::Action;
+use ui::ui;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(Parsed::Run(cli)) => cli,
+        Ok(Parsed::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Parsed::Version) => {
+            println!("sshx {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("sshx: {}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // Create app before touching the terminal so config errors are readable
+    let mut app = match App::new() {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("sshx: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+    app.config.dry_run = cli.dry_run;
+    if cli.exec {
+        app.config.launch = Launch::Exec;
+    }
+    if cli.inline {
+        app.config.inline = true;
+    }
+    if let Some(uri) = &cli.open {
+        open_uri(&mut app, uri);
+        return Ok(());
+    }
+    let picking = cli.pick.is_some();
+    app.pick = cli.pick;
+
     // Setup terminal
+    let mut output = match tui_output(picking) {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("sshx: cannot open the terminal: {}", err);
+            std::process::exit(1);
+        }
+    };
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    // Create app
-    let mut app = App::new()?;
+    if !app.config.inline {
+        execute!(output, EnterAlternateScreen)?;
+    }
+    if app.config.mouse {
+        execute!(output, EnableMouseCapture)?;
+    }
+    let backend = CrosstermBackend::new(output);
+    let viewport = if app.config.inline {
+        Viewport::Inline(app.config.inline_height)
+    } else {
+        Viewport::Fullscreen
+    };
+    let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
 
     let res = run_app(&mut terminal, &mut app);
 
     // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if app.config.inline {
+        // 擦掉列表，shell 提示符接着原来的位置显示
+        terminal.clear()?;
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    } else {
+        disable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+    }
     terminal.show_cursor()?;
 
     if let Err(err) = res {
         println!("{:?}", err);
     }
 
+    // 终端已恢复，ssh 接管当前进程，信号和退出码与直接运行相同
+    if let Some((program, args)) = app.exec.take() {
+        let err = exec(&program, &args);
+        eprintln!("sshx: failed to run {}: {}", program, err);
+        std::process::exit(1);
+    }
+
+    // 没有选择服务器时以非零状态退出，便于 shell 中判断
+    if picking {
+        match app.picked.take() {
+            Some(text) => println!("{}", text),
+            None => std::process::exit(1),
+        }
+    }
+
     Ok(())
 }
 
+/// `sshx open`: connect to a URI without the TUI, e.g. when an ssh:// link is clicked.
+fn open_uri(app: &mut App, uri: &str) {
+    let target = match Server::from_target(uri) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("sshx: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let defaults = app.connection_defaults();
+    // 当前 profile 中保存过的同一服务器带有跳板机、密钥等设置
+    let server = app
+        .servers
+        .iter()
+        .find(|s| {
+            s.driver.is_ssh()
+                && s.host == target.host
+                && (target.user.is_empty() || s.effective_user(&defaults) == target.user)
+                && target.port.is_none_or(|port| s.effective_port(&defaults) == port)
+        })
+        .cloned()
+        .unwrap_or(target);
+    let (program, args) = match server.driver.command(&server, &defaults, &app.config.programs) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("sshx: cannot connect to '{}': {}", server.name, e);
+            std::process::exit(1);
+        }
+    };
+    if app.config.dry_run {
+        println!("{}", format_command(&program, &args));
+        return;
+    }
+    app.set_last_connected(&server);
+    let err = exec(&program, &args);
+    eprintln!("sshx: failed to run {}: {}", program, err);
+    std::process::exit(1);
+}
+
+/// Where the TUI is drawn. `sshx pick` draws on the terminal itself so that stdout
+/// only carries the chosen server, e.g. inside `$(...)`.
+fn tui_output(picking: bool) -> io::Result<Box<dyn io::Write>> {
+    if !picking {
+        return Ok(Box::new(io::stdout()));
+    }
+    #[cfg(unix)]
+    {
+        let tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+        Ok(Box::new(io::BufWriter::new(tty)))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(Box::new(io::stderr()))
+    }
+}
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+// 标签页显示时检查得更快，让远端输出及时出现；只有收到新输出时才重绘
+const TAB_TICK_INTERVAL: Duration = Duration::from_millis(16);
+
 fn run_app<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut mouse = app.config.mouse;
+    let mut paste = false;
+    let mut redraw = true;
+    let mut last_draw = Instant::now();
     loop {
         // Hide cursor in normal mode, show in input/edit mode
         match app.input_mode {
-            InputMode::Normal | InputMode::ConfirmDelete(_) | InputMode::ShowMessage(_) | InputMode::SelectingProfile => terminal.hide_cursor()?,
-            InputMode::Adding(_) | InputMode::Editing(_) | InputMode::CreatingProfile(_) => terminal.show_cursor()?,
+            InputMode::Normal | InputMode::ConfirmDelete(_) | InputMode::ConfirmQuit | InputMode::ShowMessage(_) | InputMode::SelectingProfile | InputMode::Help | InputMode::BroadcastResults(_) | InputMode::RunHistory { .. } | InputMode::Watch(_) | InputMode::Tail(_) | InputMode::TmuxSelect(_) | InputMode::ConfirmSave(_) | InputMode::Share { .. } => terminal.hide_cursor()?,
+            InputMode::Form(_) | InputMode::CreatingProfile(_) | InputMode::QuickConnect { .. } | InputMode::Import { .. } => terminal.show_cursor()?,
+            InputMode::TailSetup(ref s) => match s.phase {
+                TailPhase::Source => terminal.show_cursor()?,
+                TailPhase::SelectServers => terminal.hide_cursor()?,
+            },
             InputMode::BroadcastCommand(ref s) => match s.phase {
-                BroadcastPhase::EnterCommand => terminal.show_cursor()?,
-                BroadcastPhase::SelectServers => terminal.hide_cursor()?,
+                BroadcastPhase::EnterCommand
+                | BroadcastPhase::SearchHistory { .. }
+                | BroadcastPhase::SaveSnippet(_) => terminal.show_cursor()?,
+                BroadcastPhase::Snippets { .. }
+                | BroadcastPhase::SelectServers
+                | BroadcastPhase::Preview { .. } => terminal.hide_cursor()?,
             },
         }
         
-        terminal.draw(|f| ui(f, app))?;
+        // 分享窗口打开时交还鼠标，便于直接选中文字；导入窗口把粘贴内容整段接收
+        let want_mouse = app.config.mouse && !matches!(app.input_mode, InputMode::Share { .. });
+        if want_mouse != mouse {
+            if want_mouse {
+                execute!(terminal.backend_mut(), EnableMouseCapture)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)?;
+            }
+            mouse = want_mouse;
+        }
+        let want_paste = matches!(app.input_mode, InputMode::Import { .. });
+        if want_paste != paste {
+            if want_paste {
+                execute!(terminal.backend_mut(), EnableBracketedPaste)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableBracketedPaste)?;
+            }
+            paste = want_paste;
+        }
 
-        if let Event::Key(key) = event::read()? {
+        if redraw || last_draw.elapsed() >= TICK_INTERVAL {
+            let area = ui::session_area(terminal.size()?);
+            for session in &mut app.sessions {
+                session.resize(area.height, area.width);
+            }
+            terminal.draw(|f| ui(f, app))?;
+            last_draw = Instant::now();
+        }
+        redraw = true;
+
+        // 后台有任务时定时刷新，否则一直等待输入
+        let interval = if app.active_tab.is_some() { TAB_TICK_INTERVAL } else { TICK_INTERVAL };
+        if app.is_busy() && !event::poll(interval)? {
+            // 标签页没有新输出时按 TICK_INTERVAL 刷新其他内容（提示、标签栏）
+            redraw = app.tick() || app.active_tab.is_none();
+            continue;
+        }
+        let keep_running = match event::read()? {
             // Windows会同时发送Press和Release事件，只处理Press
-            if key.kind != KeyEventKind::Press {
-                continue;
-            }
-            match &mut app.input_mode {
-                InputMode::Normal => {
-                    // Reset pending_g on any key that isn't 'g'
-                    if key.code != KeyCode::Char('g') {
-                        app.pending_g = false;
+            Event::Key(key) if key.kind == KeyEventKind::Press => handle_key(terminal, app, key)?,
+            Event::Mouse(mouse) => handle_mouse(terminal, app, mouse)?,
+            Event::Paste(pasted) => {
+                if let InputMode::Import { text, error } = &mut app.input_mode {
+                    text.push_str(&pasted.replace("\r\n", "\n").replace('\r', "\n"));
+                    *error = None;
+                }
+                true
+            }
+            _ => true,
+        };
+        app.save_finished_run();
+        if !keep_running || app.exec.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+/// Handle a key press; returns false when sshx should quit.
+fn handle_key<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    key: KeyEvent,
+) -> io::Result<bool> {
+    if app.tab_prefix {
+        app.tab_prefix = false;
+        tab_command(app, key);
+        return Ok(true);
+    }
+    // 标签页中的按键（除前缀键外）都交给会话
+    if let Some(i) = app.active_tab {
+        if app.keymap.is_key(Action::Tabs, key) {
+            app.tab_prefix = true;
+        } else if app.sessions[i].status == SessionStatus::Running {
+            app.sessions[i].send_key(key);
+        } else {
+            app.close_tab(i);
+        }
+        return Ok(true);
+    }
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match &mut app.input_mode {
+        InputMode::Normal => {
+            let Some(action) = app.resolve_key(key) else {
+                return Ok(true);
+            };
+            return perform_action(terminal, app, action);
+        },
+        InputMode::Form(form) => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::Normal,
+            KeyCode::Char(c) => form.push_char(c),
+            KeyCode::Backspace => form.pop_char(),
+            KeyCode::Tab | KeyCode::Down => form.next_field(),
+            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+            KeyCode::Left if form.current_field() == FormField::Driver => {
+                form.set_driver(form.driver.previous());
+            }
+            KeyCode::Right if form.current_field() == FormField::Driver => {
+                form.set_driver(form.driver.next());
+            }
+            KeyCode::Enter => {
+                // Validate and save
+                if form.kind == FormKind::Defaults {
+                    if let Some(defaults) = form.validate_defaults() {
+                        app.defaults = defaults;
+                        app.input_mode = save_result(app);
                     }
-                    match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
-                    KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => app.next(),
-                    KeyCode::Up | KeyCode::BackTab | KeyCode::Char('k') => app.previous(),
-                    KeyCode::Char('G') => app.select_last(),
-                    KeyCode::Char('g') => {
-                        if app.pending_g {
-                            app.select_first();
-                            app.pending_g = false;
-                        } else {
-                            app.pending_g = true;
+                } else if let Some(server) = form.validate_server(&app.servers) {
+                    match form.kind {
+                        FormKind::Edit(idx) => app.servers[idx] = server,
+                        _ => {
+                            app.servers.push(server);
+                            app.state.select(Some(app.servers.len() - 1));
                         }
                     }
-                    KeyCode::Char('e') => {
-                        // Enter profile selection mode
-                        let current = app.current_profile.clone();
-                        let idx = app.profiles.iter().position(|p| *p == current).unwrap_or(0);
-                        app.profile_state.select(Some(idx));
-                        app.input_mode = InputMode::SelectingProfile;
+                    app.input_mode = save_result(app);
+                }
+            }
+            _ => {}
+        },
+        InputMode::QuickConnect { input, error } => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::Normal,
+            KeyCode::Enter => match Server::parse_quick(input) {
+                Ok(server) => {
+                    app.input_mode = InputMode::Normal;
+                    quick_connect(terminal, app, server)?;
+                }
+                Err(e) => *error = Some(e),
+            },
+            KeyCode::Backspace => {
+                input.pop();
+                *error = None;
+            }
+            KeyCode::Char('u') if ctrl => {
+                input.clear();
+                *error = None;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                input.push(c);
+                *error = None;
+            }
+            _ => {}
+        },
+        InputMode::ConfirmSave(server) => match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                app.input_mode = InputMode::Form(ConnectionForm::prefilled(server));
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                app.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        },
+        InputMode::Share { index, format } => match key.code {
+            KeyCode::Tab => *format = format.next(),
+            KeyCode::Char('c') => {
+                let format = *format;
+                let server = &app.servers[*index];
+                match share::export(server, &app.connection_defaults(), format) {
+                    Ok(text) => {
+                        let what = format!("{} as {}", server.name, format.label());
+                        app.input_mode = InputMode::Normal;
+                        copy_to_clipboard(terminal, app, &text, &what)?;
                     }
-                    KeyCode::Char('n') => {
-                        app.input_mode = InputMode::Adding(AddingState::new());
+                    Err(e) => app.input_mode = InputMode::ShowMessage(format!("Cannot copy '{}': {}", server.name, e)),
+                }
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+            _ => {}
+        },
+        InputMode::Import { text, error } => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::Normal,
+            KeyCode::Enter => match share::import(text) {
+                Ok(servers) => {
+                    let count = servers.len();
+                    app.input_mode = match app.import_servers(servers) {
+                        Ok(()) => InputMode::ShowMessage(format!(
+                            "Imported {} server{} into {}.",
+                            count,
+                            if count == 1 { "" } else { "s" },
+                            app.current_profile
+                        )),
+                        Err(e) => InputMode::ShowMessage(format!("Failed to save the profile: {:#}", e)),
+                    };
+                }
+                Err(e) => *error = Some(e),
+            },
+            KeyCode::Backspace => {
+                text.pop();
+                *error = None;
+            }
+            KeyCode::Char('u') if ctrl => {
+                text.clear();
+                *error = None;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                text.push(c);
+                *error = None;
+            }
+            _ => {}
+        },
+        InputMode::ConfirmQuit => match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => return Ok(false),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                app.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        },
+        InputMode::ConfirmDelete(idx) => match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                let idx = *idx;
+                app.input_mode = InputMode::Normal;
+                if idx < app.servers.len() {
+                    app.servers.remove(idx);
+                    if app.servers.is_empty() {
+                        app.state.select(None);
+                    } else if idx >= app.servers.len() {
+                        app.state.select(Some(app.servers.len() - 1));
                     }
-                    KeyCode::Char('c') => {
-                        // SSH Copy ID
-                        if let Some(idx) = app.state.selected() {
-                            let server = app.servers[idx].clone();
-                            let args = server.to_copy_id_args();
-                            run_ssh_copy_id(terminal, &args)?;
-                            app.set_last_connected(&server);
-                        }
+                    app.input_mode = save_result(app);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                app.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        },
+        InputMode::ShowMessage(_) => match key.code {
+            KeyCode::Enter | KeyCode::Esc | KeyCode::Char(' ') => {
+                app.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        },
+        InputMode::BroadcastCommand(state) => match &mut state.phase {
+            BroadcastPhase::EnterCommand => match key.code {
+                KeyCode::Esc => app.input_mode = InputMode::Normal,
+                KeyCode::Char('r') if ctrl => {
+                    let found = app.history.search("", app.history.entries().len());
+                    state.phase = BroadcastPhase::SearchHistory { query: String::new(), found };
+                }
+                KeyCode::Char('o') if ctrl => {
+                    state.phase = BroadcastPhase::Snippets { cursor: 0 };
+                }
+                KeyCode::Char('s') if ctrl && !state.command.is_empty() => {
+                    state.phase = BroadcastPhase::SaveSnippet(state.snippet_name.clone().unwrap_or_default());
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    state.command.push(c);
+                    state.history_idx = None;
+                }
+                KeyCode::Backspace => {
+                    state.command.pop();
+                    state.history_idx = None;
+                }
+                KeyCode::Up => state.history_previous(app.history.entries()),
+                KeyCode::Down => state.history_next(app.history.entries()),
+                KeyCode::Enter if !state.command.is_empty() => {
+                    state.phase = BroadcastPhase::SelectServers;
+                }
+                _ => {}
+            },
+            BroadcastPhase::SearchHistory { query, found } => match key.code {
+                KeyCode::Esc => state.phase = BroadcastPhase::EnterCommand,
+                // Ctrl+R again looks for an older match
+                KeyCode::Char('r') if ctrl => {
+                    if let Some(older) = found.and_then(|i| app.history.search(query, i)) {
+                        *found = Some(older);
                     }
-                    KeyCode::Char('d') => {
-                        // 进入删除确认模式
-                        if let Some(idx) = app.state.selected() {
-                            app.input_mode = InputMode::ConfirmDelete(idx);
-                        }
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    query.push(c);
+                    *found = app.history.search(query, app.history.entries().len());
+                }
+                KeyCode::Backspace => {
+                    query.pop();
+                    *found = app.history.search(query, app.history.entries().len());
+                }
+                KeyCode::Enter => {
+                    if let Some(i) = *found {
+                        let command = app.history.entries()[i].clone();
+                        state.set_command(&command);
                     }
-                    KeyCode::Char('i') => {
-                        // Edit server
-                        if let Some(idx) = app.state.selected() {
-                            let server = &app.servers[idx].clone();
-                            app.input_mode = InputMode::Editing(EditingState::new(server, idx));
-                        }
+                    state.phase = BroadcastPhase::EnterCommand;
+                }
+                _ => {}
+            },
+            BroadcastPhase::Snippets { cursor } => match key.code {
+                KeyCode::Esc => state.phase = BroadcastPhase::EnterCommand,
+                KeyCode::Char('j') | KeyCode::Down if *cursor + 1 < app.snippets.items().len() => {
+                    *cursor += 1;
+                }
+                KeyCode::Char('k') | KeyCode::Up if *cursor > 0 => *cursor -= 1,
+                KeyCode::Char('d') | KeyCode::Delete if *cursor < app.snippets.items().len() => {
+                    if let Err(e) = app.snippets.remove(*cursor) {
+                        app.input_mode = InputMode::ShowMessage(format!("Failed to save snippets: {:#}", e));
+                        return Ok(true);
                     }
-                    KeyCode::Char('m') => {
-                        // Mosh
-                        if let Some(idx) = app.state.selected() {
-                            // Check if mosh is installed
-                            if !is_command_available("mosh") {
-                                app.input_mode = InputMode::ShowMessage(
-                                    "Mosh is not installed on your system.\n\nPlease install mosh first:\n- Linux: sudo apt install mosh / sudo yum install mosh\n- macOS: brew install mosh\n- Windows: Install via package manager or from mosh.org".to_string()
-                                );
-                            } else {
-                                let server = app.servers[idx].clone();
-                                if !server.jump_host.is_empty() {
-                                    app.input_mode = InputMode::ShowMessage(
-                                        "Mosh does not support jump host connections.\nPlease use SSH (Enter) instead.".to_string()
-                                    );
-                                } else {
-                                    let args = server.to_mosh_args();
-                                    run_external_command(terminal, "mosh", &args)?;
-                                    app.set_last_connected(&server);
-                                }
-                            }
-                        }
+                    *cursor = (*cursor).min(app.snippets.items().len().saturating_sub(1));
+                }
+                KeyCode::Enter if *cursor < app.snippets.items().len() => {
+                    let snippet = app.snippets.items()[*cursor].clone();
+                    state.set_command(&snippet.command);
+                    state.snippet_name = Some(snippet.name);
+                    state.phase = BroadcastPhase::EnterCommand;
+                }
+                _ => {}
+            },
+            BroadcastPhase::SaveSnippet(name) => match key.code {
+                KeyCode::Esc => state.phase = BroadcastPhase::EnterCommand,
+                KeyCode::Char(c) if !ctrl => name.push(c),
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Enter if !name.trim().is_empty() => {
+                    let name = name.trim().to_string();
+                    if let Err(e) = app.snippets.save(&name, &state.command) {
+                        app.input_mode = InputMode::ShowMessage(format!("Failed to save snippets: {:#}", e));
+                        return Ok(true);
                     }
-                    KeyCode::Char('s') => {
-                        // SFTP
-                        if let Some(idx) = app.state.selected() {
-                            let server = app.servers[idx].clone();
-                            let args = server.to_sftp_args();
-                            run_external_command(terminal, "sftp", &args)?;
-                            app.set_last_connected(&server);
+                    state.snippet_name = Some(name);
+                    state.phase = BroadcastPhase::EnterCommand;
+                }
+                _ => {}
+            },
+            BroadcastPhase::SelectServers => match key.code {
+                KeyCode::Esc => app.input_mode = InputMode::Normal,
+                KeyCode::Char('j') | KeyCode::Down => state.targets.next(),
+                KeyCode::Char('k') | KeyCode::Up => state.targets.previous(),
+                // Rollout: batch size (0 = all), pause between batches, failure policy
+                KeyCode::Char('+') | KeyCode::Char('=') => state.options.batch_size += 1,
+                KeyCode::Char('-') => state.options.batch_size = state.options.batch_size.saturating_sub(1),
+                KeyCode::Char('>') => state.options.pause += 5,
+                KeyCode::Char('<') => state.options.pause = state.options.pause.saturating_sub(5),
+                KeyCode::Char('f') => state.options.on_failure = state.options.on_failure.next(),
+                KeyCode::Char(']') => state.options.interval += 1,
+                KeyCode::Char('[') if state.options.interval > 1 => state.options.interval -= 1,
+                KeyCode::Char(' ') => state.targets.toggle(state.targets.cursor, &app.servers),
+                // w 与 Enter 相同，但以 watch 模式反复执行
+                KeyCode::Enter | KeyCode::Char('w') => {
+                    state.watch = key.code == KeyCode::Char('w');
+                    let watch = state.watch;
+                    let template = state.command.clone();
+                    let options = state.options;
+                    let targets = state.targets.indices();
+                    // 每个目标单独展开 {name} 等占位符
+                    let defaults = app.connection_defaults();
+                    let mut expanded = false;
+                    let targets: Vec<(usize, Vec<String>)> = targets
+                        .into_iter()
+                        .map(|i| {
+                            let server = &app.servers[i];
+                            let command = template::expand_command(&template, &server.template_vars(&defaults));
+                            expanded |= command != template;
+                            (i, server.to_broadcast_args(&defaults, command))
+                        })
+                        .collect();
+                    let preview = expanded || app.config.broadcast_preview || app.config.dry_run;
+                    if preview && !targets.is_empty() {
+                        if let InputMode::BroadcastCommand(state) = &mut app.input_mode {
+                            state.phase = BroadcastPhase::Preview { targets, cursor: 0 };
                         }
+                    } else {
+                        start_broadcast(terminal, app, &template, targets, options, watch)?;
                     }
-                    KeyCode::Enter => {
-                        // SSH
-                        if let Some(idx) = app.state.selected() {
-                            let server = app.servers[idx].clone();
-                            let args = server.to_ssh_args();
-                            run_external_command(terminal, "ssh", &args)?;
-                            app.set_last_connected(&server);
-                        }
+                }
+                _ => {}
+            },
+            BroadcastPhase::Preview { targets, cursor } => match key.code {
+                KeyCode::Esc => state.phase = BroadcastPhase::SelectServers,
+                KeyCode::Char('j') | KeyCode::Down if *cursor + 1 < targets.len() => *cursor += 1,
+                KeyCode::Char('k') | KeyCode::Up if *cursor > 0 => *cursor -= 1,
+                KeyCode::Enter => {
+                    let template = state.command.clone();
+                    let (options, watch) = (state.options, state.watch);
+                    let targets = std::mem::take(targets);
+                    start_broadcast(terminal, app, &template, targets, options, watch)?;
+                }
+                _ => {}
+            },
+        },
+        InputMode::BroadcastResults(view) => {
+            let count = view.row_count();
+            let has_output = view.selected_host().is_some();
+            match (key.code, view.output_scroll.as_mut()) {
+                // 输出窗口打开时方向键滚动输出
+                (KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q'), Some(_)) => view.close_output(),
+                (KeyCode::Char('j') | KeyCode::Down, Some(scroll)) => *scroll = scroll.saturating_add(1),
+                (KeyCode::Char('k') | KeyCode::Up, Some(scroll)) => *scroll = scroll.saturating_sub(1),
+                (KeyCode::Char('j') | KeyCode::Down, None) if view.cursor + 1 < count => view.cursor += 1,
+                (KeyCode::Char('k') | KeyCode::Up, None) if view.cursor > 0 => view.cursor -= 1,
+                (KeyCode::Enter, None) if has_output => view.output_scroll = Some(0),
+                (KeyCode::Char('g'), None) => view.toggle_grouped(),
+                (KeyCode::Char('d'), None) => view.mark_diff(),
+                (KeyCode::Char('e'), None) => view.export(ReportFormat::Json),
+                (KeyCode::Char('m'), None) => view.export(ReportFormat::Markdown),
+                (KeyCode::Char('c'), None) => view.rollout.resume(),
+                (KeyCode::Char('s'), None) => view.rollout.stop(),
+                (KeyCode::Esc | KeyCode::Char('q'), None)
+                    if view.rollout.is_active() || view.rollout.state == RolloutState::AwaitingDecision =>
+                {
+                    view.rollout.stop()
+                }
+                (KeyCode::Esc | KeyCode::Char('q'), None) => app.input_mode = InputMode::Normal,
+                _ => {}
+            }
+        }
+        InputMode::Watch(view) => {
+            let count = view.watch.hosts.len();
+            match (key.code, view.output_scroll.as_mut()) {
+                (KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q'), Some(_)) => view.output_scroll = None,
+                (KeyCode::Char('j') | KeyCode::Down, Some(scroll)) => *scroll = scroll.saturating_add(1),
+                (KeyCode::Char('k') | KeyCode::Up, Some(scroll)) => *scroll = scroll.saturating_sub(1),
+                (KeyCode::Char('j') | KeyCode::Down, None) if view.cursor + 1 < count => view.cursor += 1,
+                (KeyCode::Char('k') | KeyCode::Up, None) if view.cursor > 0 => view.cursor -= 1,
+                (KeyCode::Enter, None) if count > 0 => view.output_scroll = Some(0),
+                (KeyCode::Char(' '), None) => view.watch.paused = !view.watch.paused,
+                (KeyCode::Char('r'), None) => view.watch.run_now(),
+                (KeyCode::Char('+') | KeyCode::Char('='), None) => {
+                    let interval = view.watch.interval + Duration::from_secs(1);
+                    view.watch.set_interval(interval);
+                }
+                (KeyCode::Char('-'), None) if view.watch.interval > Duration::from_secs(1) => {
+                    let interval = view.watch.interval - Duration::from_secs(1);
+                    view.watch.set_interval(interval);
+                }
+                // 关闭时 watch 被丢弃，正在执行的命令随之被终止
+                (KeyCode::Esc | KeyCode::Char('q'), None) => app.input_mode = InputMode::Normal,
+                _ => {}
+            }
+        }
+        InputMode::TailSetup(setup) => match setup.phase {
+            TailPhase::Source => match key.code {
+                KeyCode::Esc => app.input_mode = InputMode::Normal,
+                KeyCode::Tab => setup.source = setup.source.toggle(),
+                KeyCode::Char(c) => setup.target.push(c),
+                KeyCode::Backspace => { setup.target.pop(); }
+                KeyCode::Enter if !setup.target.trim().is_empty() => setup.phase = TailPhase::SelectServers,
+                _ => {}
+            },
+            TailPhase::SelectServers => match key.code {
+                KeyCode::Esc => setup.phase = TailPhase::Source,
+                KeyCode::Char('j') | KeyCode::Down => setup.targets.next(),
+                KeyCode::Char('k') | KeyCode::Up => setup.targets.previous(),
+                KeyCode::Char(' ') => setup.targets.toggle(setup.targets.cursor, &app.servers),
+                KeyCode::Enter => {
+                    let (source, target, indices) = (setup.source, setup.target.trim().to_string(), setup.targets.indices());
+                    start_tail(terminal, app, source, &target, indices)?;
+                }
+                _ => {}
+            },
+        },
+        InputMode::TmuxSelect(targets) => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::Normal,
+            KeyCode::Char('j') | KeyCode::Down => targets.next(),
+            KeyCode::Char('k') | KeyCode::Up => targets.previous(),
+            KeyCode::Char(' ') => targets.toggle(targets.cursor, &app.servers),
+            KeyCode::Enter => {
+                let indices = targets.indices();
+                open_tmux_panes(terminal, app, indices)?;
+            }
+            _ => {}
+        },
+        InputMode::Tail(view) => {
+            view.notice = None;
+            match (key.code, view.filter_input.as_mut()) {
+                (KeyCode::Esc, Some(_)) => view.filter_input = None,
+                (KeyCode::Enter, Some(_)) => view.apply_filter(),
+                (KeyCode::Backspace, Some(input)) => { input.pop(); }
+                (KeyCode::Char(c), Some(input)) => input.push(c),
+                (KeyCode::Char('/'), None) => {
+                    view.filter_input = Some(view.filter.as_ref().map(|re| re.as_str().to_string()).unwrap_or_default());
+                }
+                (KeyCode::Char(' '), None) => view.tail.toggle_pause(),
+                (KeyCode::Char('s'), None) => view.save(),
+                (KeyCode::Char('k') | KeyCode::Up, None) => view.scroll_up(1),
+                (KeyCode::Char('j') | KeyCode::Down, None) => view.scroll = view.scroll.saturating_sub(1),
+                (KeyCode::PageUp, None) => view.scroll_up(10),
+                (KeyCode::PageDown, None) => view.scroll = view.scroll.saturating_sub(10),
+                (KeyCode::Char('G') | KeyCode::End, None) => view.scroll = 0,
+                // 丢弃 TailView 时会结束所有远程命令
+                (KeyCode::Esc | KeyCode::Char('q'), None) => app.input_mode = InputMode::Normal,
+                _ => {}
+            }
+        }
+        InputMode::RunHistory { runs, cursor } => match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => app.input_mode = InputMode::Normal,
+            KeyCode::Char('j') | KeyCode::Down if *cursor + 1 < runs.len() => *cursor += 1,
+            KeyCode::Char('k') | KeyCode::Up if *cursor > 0 => *cursor -= 1,
+            KeyCode::Enter => {
+                if let Some(run) = runs.get(*cursor) {
+                    app.input_mode = InputMode::BroadcastResults(ResultsView::past(run.to_rollout()));
+                }
+            }
+            _ => {}
+        },
+        InputMode::Help => match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('?') => {
+                app.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        },
+        InputMode::SelectingProfile => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::Normal,
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => app.next_profile(),
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::BackTab => app.previous_profile(),
+            KeyCode::Char('n') => {
+                app.input_mode = InputMode::CreatingProfile(String::new());
+            }
+            KeyCode::Enter => {
+                app.input_mode = InputMode::Normal;
+                if let Some(idx) = app.profile_state.selected() {
+                    let profile = app.profiles[idx].clone();
+                    let _ = app.load_profile(&profile);
+                }
+            }
+            _ => {}
+        },
+        InputMode::CreatingProfile(name) => match key.code {
+            KeyCode::Esc => app.input_mode = InputMode::SelectingProfile,
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::Backspace => { name.pop(); }
+            KeyCode::Enter if !name.is_empty() => {
+                let mut profile_name = name.clone();
+                if !profile_name.ends_with(".json") {
+                    profile_name.push_str(".json");
+                }
+                if !app.profiles.contains(&profile_name) {
+                    app.profiles.push(profile_name.clone());
+                    app.profiles.sort();
+                }
+                app.input_mode = InputMode::Normal;
+                let _ = app.load_profile(&profile_name);
+            }
+            _ => {}
+        },
+    }
+    Ok(true)
+}
+
+/// Handle a mouse event; returns false when sshx should quit.
+fn handle_mouse<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    mouse: MouseEvent,
+) -> io::Result<bool> {
+    let (column, row) = (mouse.column, mouse.row);
+    let areas = &app.click_areas;
+    if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+        if let Some(position) = areas.tab_bar.iter().position(|r| ClickAreas::item_at(*r, 0, column, row).is_some()) {
+            app.show_tab(position);
+            return Ok(true);
+        }
+    }
+    if let Some(i) = app.active_tab {
+        match mouse.kind {
+            MouseEventKind::ScrollUp => app.sessions[i].scroll_by(3),
+            MouseEventKind::ScrollDown => app.sessions[i].scroll_by(-3),
+            _ => {}
+        }
+        return Ok(true);
+    }
+    match &mut app.input_mode {
+        InputMode::Normal => match mouse.kind {
+            MouseEventKind::ScrollDown => app.next(),
+            MouseEventKind::ScrollUp => app.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let offset = app.state.offset();
+                if let Some(idx) = ClickAreas::item_at(areas.server_list, offset, column, row)
+                    .filter(|idx| *idx < app.servers.len())
+                {
+                    app.state.select(Some(idx));
+                    if app.register_click(idx) {
+                        return perform_action(terminal, app, Action::Connect);
                     }
-                    KeyCode::Char('p') => {
-                        if !app.servers.is_empty() {
-                            app.input_mode = InputMode::BroadcastCommand(
-                                BroadcastState::new(app.servers.len())
-                            );
+                }
+            }
+            _ => {}
+        },
+        InputMode::Form(form) => {
+            if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+                if let Some(idx) = areas.form_fields.iter().position(|r| {
+                    ClickAreas::item_at(*r, 0, column, row).is_some()
+                }) {
+                    form.field_idx = idx;
+                }
+            }
+        }
+        InputMode::BroadcastCommand(state) => match &mut state.phase {
+            BroadcastPhase::Snippets { cursor } => {
+                let count = app.snippets.items().len();
+                match mouse.kind {
+                    MouseEventKind::ScrollDown if *cursor + 1 < count => *cursor += 1,
+                    MouseEventKind::ScrollUp if *cursor > 0 => *cursor -= 1,
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, column, row)
+                            .filter(|idx| *idx < count)
+                        {
+                            *cursor = idx;
                         }
                     }
                     _ => {}
+                }
+            }
+            BroadcastPhase::Preview { targets, cursor } => match mouse.kind {
+                MouseEventKind::ScrollDown if *cursor + 1 < targets.len() => *cursor += 1,
+                MouseEventKind::ScrollUp if *cursor > 0 => *cursor -= 1,
+                _ => {}
+            },
+            BroadcastPhase::SelectServers => select_targets_mouse(&mut state.targets, &app.servers, areas, mouse),
+            _ => {}
+        },
+        InputMode::BroadcastResults(view) => {
+            let count = view.row_count();
+            match (mouse.kind, view.output_scroll.as_mut()) {
+                (MouseEventKind::ScrollDown, Some(scroll)) => *scroll = scroll.saturating_add(1),
+                (MouseEventKind::ScrollUp, Some(scroll)) => *scroll = scroll.saturating_sub(1),
+                (MouseEventKind::ScrollDown, None) if view.cursor + 1 < count => view.cursor += 1,
+                (MouseEventKind::ScrollUp, None) if view.cursor > 0 => view.cursor -= 1,
+                (MouseEventKind::Down(MouseButton::Left), None) => {
+                    if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, column, row)
+                        .filter(|idx| *idx < count)
+                    {
+                        view.cursor = idx;
                     }
-                },
-                InputMode::Editing(state) => match key.code {
-                    KeyCode::Esc => app.input_mode = InputMode::Normal,
-                    KeyCode::Char(c) => {
-                        match state.field_idx {
-                            0 => state.name.push(c),
-                            1 => state.user.push(c),
-                            2 => state.host.push(c),
-                            3 => state.port.push(c),
-                            4 => state.jump_host.push(c),
-                            _ => {}
-                        }
+                }
+                _ => {}
+            }
+        }
+        InputMode::Watch(view) => {
+            let count = view.watch.hosts.len();
+            match (mouse.kind, view.output_scroll.as_mut()) {
+                (MouseEventKind::ScrollDown, Some(scroll)) => *scroll = scroll.saturating_add(1),
+                (MouseEventKind::ScrollUp, Some(scroll)) => *scroll = scroll.saturating_sub(1),
+                (MouseEventKind::ScrollDown, None) if view.cursor + 1 < count => view.cursor += 1,
+                (MouseEventKind::ScrollUp, None) if view.cursor > 0 => view.cursor -= 1,
+                (MouseEventKind::Down(MouseButton::Left), None) => {
+                    if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, column, row)
+                        .filter(|idx| *idx < count)
+                    {
+                        view.cursor = idx;
                     }
-                    KeyCode::Backspace => {
-                        match state.field_idx {
-                            0 => { state.name.pop(); },
-                            1 => { state.user.pop(); },
-                            2 => { state.host.pop(); },
-                            3 => { state.port.pop(); },
-                            4 => { state.jump_host.pop(); },
-                            _ => {}
-                        }
+                }
+                _ => {}
+            }
+        }
+        InputMode::TailSetup(setup) if matches!(setup.phase, TailPhase::SelectServers) => {
+            select_targets_mouse(&mut setup.targets, &app.servers, areas, mouse);
+        }
+        InputMode::TmuxSelect(targets) => select_targets_mouse(targets, &app.servers, areas, mouse),
+        InputMode::Tail(view) => match mouse.kind {
+            MouseEventKind::ScrollUp => view.scroll_up(3),
+            MouseEventKind::ScrollDown => view.scroll = view.scroll.saturating_sub(3),
+            _ => {}
+        },
+        InputMode::RunHistory { runs, cursor } => match mouse.kind {
+            MouseEventKind::ScrollDown if *cursor + 1 < runs.len() => *cursor += 1,
+            MouseEventKind::ScrollUp if *cursor > 0 => *cursor -= 1,
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, column, row)
+                    .filter(|idx| *idx < runs.len())
+                {
+                    *cursor = idx;
+                    let rollout = runs[idx].to_rollout();
+                    if app.register_click(idx) {
+                        app.input_mode = InputMode::BroadcastResults(ResultsView::past(rollout));
                     }
-                    KeyCode::Tab | KeyCode::Down => {
-                        if state.field_idx < 4 {
-                            state.field_idx += 1;
-                        } else {
-                            state.field_idx = 0;
-                        }
+                }
+            }
+            _ => {}
+        },
+        InputMode::SelectingProfile => match mouse.kind {
+            MouseEventKind::ScrollDown => app.next_profile(),
+            MouseEventKind::ScrollUp => app.previous_profile(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, column, row)
+                    .filter(|idx| *idx < app.profiles.len())
+                {
+                    app.profile_state.select(Some(idx));
+                    if app.register_click(idx) {
+                        let profile = app.profiles[idx].clone();
+                        app.input_mode = InputMode::Normal;
+                        let _ = app.load_profile(&profile);
                     }
-                    KeyCode::BackTab | KeyCode::Up => {
-                        if state.field_idx > 0 {
-                            state.field_idx -= 1;
-                        } else {
-                            state.field_idx = 4;
-                        }
+                }
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Scroll through the target list, or move the cursor to a clicked server and toggle it.
+fn select_targets_mouse(targets: &mut TargetSelection, servers: &[Server], areas: &ClickAreas, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::ScrollDown => targets.next(),
+        MouseEventKind::ScrollUp => targets.previous(),
+        MouseEventKind::Down(MouseButton::Left) => {
+            if let Some(idx) = ClickAreas::item_at(areas.popup_list, areas.popup_offset, mouse.column, mouse.row)
+                .filter(|idx| *idx < servers.len())
+            {
+                targets.cursor = idx;
+                targets.toggle(idx, servers);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Run a server list action; returns false when sshx should quit.
+fn perform_action<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    action: Action,
+) -> io::Result<bool> {
+    if let Some(format) = &app.pick {
+        match action {
+            Action::Connect | Action::ConnectSsh => {
+                let Some(idx) = app.state.selected() else {
+                    return Ok(true);
+                };
+                let server = &app.servers[idx];
+                match format.render(server, &app.connection_defaults(), &app.config.programs) {
+                    Ok(text) => {
+                        app.picked = Some(text);
+                        return Ok(false);
                     }
-                    KeyCode::Enter => {
-                        // Validate and save
-                        if !state.name.is_empty() && !state.host.is_empty() {
-                            let updated_server = Server {
-                                name: state.name.clone(),
-                                user: if state.user.is_empty() { "root".to_string() } else { state.user.clone() },
-                                host: state.host.clone(),
-                                port: if state.port.is_empty() { "22".to_string() } else { state.port.clone() },
-                                jump_host: state.jump_host.clone(),
-                            };
-                            app.servers[state.server_index] = updated_server;
-                            let _ = app.save();
-                            app.input_mode = InputMode::Normal;
-                        }
+                    Err(e) => {
+                        app.input_mode = InputMode::ShowMessage(format!("Cannot print '{}': {}", server.name, e));
                     }
-                    _ => {}
-                },
-                InputMode::Adding(state) => match key.code {
-                    KeyCode::Esc => app.input_mode = InputMode::Normal,
-                    KeyCode::Char(c) => {
-                        match state.field_idx {
-                            0 => state.name.push(c),
-                            1 => state.user.push(c),
-                            2 => state.host.push(c),
-                            3 => state.port.push(c),
-                            4 => state.jump_host.push(c),
-                            _ => {}
+                }
+                return Ok(true);
+            }
+            // 这些操作会暂停 TUI 并向标准输出写入内容
+            Action::ConnectSftp
+            | Action::ConnectMosh
+            | Action::CopyId
+            | Action::Broadcast
+            | Action::Tail
+            | Action::Custom(_)
+            | Action::Tmux
+            | Action::OpenTab
+            | Action::QuickConnect => {
+                app.input_mode = InputMode::ShowMessage(
+                    "Not available while picking a server.\nPress Enter on a server to print it.".to_string(),
+                );
+                return Ok(true);
+            }
+            _ => {}
+        }
+    }
+    match action {
+        Action::Quit if app.config.confirm_quit || !app.sessions.is_empty() => {
+            app.input_mode = InputMode::ConfirmQuit;
+        }
+        Action::Quit => return Ok(false),
+        Action::Connect => {
+            // 非 ssh driver 忽略 default_protocol，直接使用 driver 自己的命令
+            if let Some(idx) = app.state.selected() {
+                let server = app.servers[idx].clone();
+                if server.driver != Driver::Ssh {
+                    match server.driver.command(&server, &app.connection_defaults(), &app.config.programs) {
+                        Ok((program, args)) => {
+                            if launch_connection(terminal, app, &server.name, &program, &args)? {
+                                app.set_last_connected(&server);
+                            }
                         }
-                    }
-                    KeyCode::Backspace => {
-                        match state.field_idx {
-                            0 => { state.name.pop(); },
-                            1 => { state.user.pop(); },
-                            2 => { state.host.pop(); },
-                            3 => { state.port.pop(); },
-                            4 => { state.jump_host.pop(); },
-                            _ => {}
+                        Err(e) => {
+                            app.input_mode = InputMode::ShowMessage(format!(
+                                "Cannot connect to '{}': {}",
+                                server.name, e
+                            ));
                         }
                     }
-                    KeyCode::Tab | KeyCode::Down => {
-                        if state.field_idx < 4 {
-                            state.field_idx += 1;
-                        } else {
-                            state.field_idx = 0;
+                    return Ok(true);
+                }
+            }
+            let action = match app.config.default_protocol {
+                Protocol::Ssh => Action::ConnectSsh,
+                Protocol::Sftp => Action::ConnectSftp,
+                Protocol::Mosh => Action::ConnectMosh,
+            };
+            return perform_action(terminal, app, action);
+        }
+        Action::Next => app.next(),
+        Action::Previous => app.previous(),
+        Action::SelectFirst => app.select_first(),
+        Action::SelectLast => app.select_last(),
+        Action::Help => app.input_mode = InputMode::Help,
+        Action::SelectProfile => {
+            // Enter profile selection mode
+            let current = app.current_profile.clone();
+            let idx = app.profiles.iter().position(|p| *p == current).unwrap_or(0);
+            app.profile_state.select(Some(idx));
+            app.input_mode = InputMode::SelectingProfile;
+        }
+        Action::NewServer => {
+            app.input_mode = InputMode::Form(ConnectionForm::new());
+        }
+        Action::EditDefaults => {
+            // Edit profile defaults
+            app.input_mode = InputMode::Form(ConnectionForm::defaults(&app.defaults));
+        }
+        Action::CopyId => {
+            // SSH Copy ID
+            if let Some(idx) = app.state.selected().filter(|&idx| require_ssh(app, idx, "ssh-copy-id")) {
+                let server = app.servers[idx].clone();
+                let args = server.to_copy_id_args(&app.connection_defaults());
+                run_ssh_copy_id(terminal, &app.config, &args)?;
+                app.set_last_connected(&server);
+            }
+        }
+        Action::CopyCommand => {
+            if let Some(idx) = app.state.selected().filter(|&idx| require_ssh(app, idx, "Copying the ssh command")) {
+                let server = &app.servers[idx];
+                let command = format_command(&app.config.programs.ssh, &server.to_ssh_args(&app.connection_defaults()));
+                let what = format!("the ssh command for {}", server.name);
+                copy_to_clipboard(terminal, app, &command, &what)?;
+            }
+        }
+        Action::CopyTarget | Action::CopyHost => {
+            if let Some(idx) = app.state.selected() {
+                let server = &app.servers[idx];
+                if server.host.is_empty() {
+                    app.input_mode = InputMode::ShowMessage(format!("'{}' has no host to copy.", server.name));
+                    return Ok(true);
+                }
+                let text = if action == Action::CopyTarget {
+                    server.address(&app.connection_defaults())
+                } else {
+                    server.host.clone()
+                };
+                copy_to_clipboard(terminal, app, &text, &text)?;
+            }
+        }
+        Action::DeleteServer => {
+            // 进入删除确认模式
+            if let Some(idx) = app.state.selected() {
+                app.input_mode = InputMode::ConfirmDelete(idx);
+            }
+        }
+        Action::EditServer => {
+            // Edit server
+            if let Some(idx) = app.state.selected() {
+                app.input_mode = InputMode::Form(ConnectionForm::edit(&app.servers[idx], idx));
+            }
+        }
+        Action::ConnectMosh => {
+            // Mosh
+            if let Some(idx) = app.state.selected().filter(|&idx| require_ssh(app, idx, "mosh")) {
+                // Check if mosh is installed
+                if !is_command_available(&app.config.programs.mosh) {
+                    app.input_mode = InputMode::ShowMessage(
+                        "Mosh is not installed on your system.\n\nPlease install mosh first:\n- Linux: sudo apt install mosh / sudo yum install mosh\n- macOS: brew install mosh\n- Windows: Install via package manager or from mosh.org".to_string()
+                    );
+                } else {
+                    let server = app.servers[idx].clone();
+                    if server.effective_jump_host(&app.defaults).is_some() {
+                        // 按当前键位提示，Enter 只有在默认协议是 ssh 时才走 ssh
+                        let mut keys = app.keymap.keys_for(Action::ConnectSsh);
+                        if app.config.default_protocol == Protocol::Ssh {
+                            keys.extend(app.keymap.keys_for(Action::Connect));
                         }
-                    }
-                    KeyCode::BackTab | KeyCode::Up => {
-                        if state.field_idx > 0 {
-                            state.field_idx -= 1;
+                        let hint = if keys.is_empty() {
+                            "Bind the 'ssh' action under [keys] to connect with ssh instead.".to_string()
                         } else {
-                            state.field_idx = 4;
-                        }
-                    }
-                    KeyCode::Enter => {
-                        // Validate and save
-                        if !state.name.is_empty() && !state.host.is_empty() {
-                            let new_server = Server {
-                                name: state.name.clone(),
-                                user: if state.user.is_empty() { "root".to_string() } else { state.user.clone() },
-                                host: state.host.clone(),
-                                port: if state.port.is_empty() { "22".to_string() } else { state.port.clone() },
-                                jump_host: state.jump_host.clone(),
-                            };
-                            app.servers.push(new_server);
-                            let _ = app.save();
-                            app.state.select(Some(app.servers.len() - 1));
-                            app.input_mode = InputMode::Normal;
+                            format!("Please use SSH ({}) instead.", keys.join(" or "))
+                        };
+                        app.input_mode = InputMode::ShowMessage(format!(
+                            "Mosh does not support jump host connections.\n{}",
+                            hint
+                        ));
+                    } else {
+                        let args = server.to_mosh_args(&app.connection_defaults(), &app.config.programs.ssh);
+                        let program = app.config.programs.mosh.clone();
+                        if launch_connection(terminal, app, &server.name, &program, &args)? {
+                            app.set_last_connected(&server);
                         }
                     }
-                    _ => {}
-                },
-                InputMode::ConfirmDelete(idx) => match key.code {
-                    KeyCode::Char('y') | KeyCode::Char('Y') => {
-                        let idx = *idx;
-                        app.input_mode = InputMode::Normal;
-                        if idx < app.servers.len() {
-                            app.servers.remove(idx);
-                            if app.servers.is_empty() {
-                                app.state.select(None);
-                            } else if idx >= app.servers.len() {
-                                app.state.select(Some(app.servers.len() - 1));
+                }
+            }
+        }
+        Action::ConnectSftp => {
+            // SFTP
+            if let Some(idx) = app.state.selected().filter(|&idx| require_ssh(app, idx, "sftp")) {
+                let server = app.servers[idx].clone();
+                let args = server.to_sftp_args(&app.connection_defaults());
+                let program = app.config.programs.sftp.clone();
+                if launch_connection(terminal, app, &server.name, &program, &args)? {
+                    app.set_last_connected(&server);
+                }
+            }
+        }
+        Action::ConnectSsh => {
+            // SSH
+            if let Some(idx) = app.state.selected().filter(|&idx| require_ssh(app, idx, "ssh")) {
+                let server = app.servers[idx].clone();
+                let args = server.to_ssh_args(&app.connection_defaults());
+                let program = app.config.programs.ssh.clone();
+                if launch_connection(terminal, app, &server.name, &program, &args)? {
+                    app.set_last_connected(&server);
+                }
+            }
+        }
+        Action::Custom(i) => {
+            if let Some(idx) = app.state.selected() {
+                let server = app.servers[idx].clone();
+                let action = &app.config.actions[i];
+                let background = action.background;
+                let label = action.label.clone();
+                let words = template::expand_words(
+                    &action.command,
+                    &server.template_vars(&app.connection_defaults()),
+                );
+                match words {
+                    Ok(mut words) if !words.is_empty() => {
+                        let program = words.remove(0);
+                        if background && app.config.dry_run {
+                            app.input_mode = InputMode::ShowMessage(format!(
+                                "Dry run: would start in the background:\n\n{}",
+                                format_command(&program, &words)
+                            ));
+                        } else if background {
+                            if let Err(e) = spawn_background(&program, &words) {
+                                app.input_mode = InputMode::ShowMessage(format!(
+                                    "Failed to start {}: {}",
+                                    program, e
+                                ));
                             }
-                            let _ = app.save();
+                        } else {
+                            launch_connection(terminal, app, &server.name, &program, &words)?;
                         }
                     }
-                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
-                        app.input_mode = InputMode::Normal;
-                    }
-                    _ => {}
-                },
-                InputMode::ShowMessage(_) => match key.code {
-                    KeyCode::Enter | KeyCode::Esc | KeyCode::Char(' ') => {
-                        app.input_mode = InputMode::Normal;
+                    Ok(_) => {
+                        app.input_mode = InputMode::ShowMessage(format!(
+                            "'{}' expands to an empty command for {}",
+                            label, server.name
+                        ));
                     }
-                    _ => {}
-                },
-                InputMode::BroadcastCommand(state) => match state.phase {
-                    BroadcastPhase::EnterCommand => match key.code {
-                        KeyCode::Esc => app.input_mode = InputMode::Normal,
-                        KeyCode::Char(c) => state.command.push(c),
-                        KeyCode::Backspace => { state.command.pop(); }
-                        KeyCode::Enter => {
-                            if !state.command.is_empty() {
-                                state.phase = BroadcastPhase::SelectServers;
-                            }
-                        }
-                        _ => {}
-                    },
-                    BroadcastPhase::SelectServers => match key.code {
-                        KeyCode::Esc => app.input_mode = InputMode::Normal,
-                        KeyCode::Char('j') | KeyCode::Down => {
-                            if state.cursor + 1 < app.servers.len() {
-                                state.cursor += 1;
-                            }
-                        }
-                        KeyCode::Char('k') | KeyCode::Up => {
-                            if state.cursor > 0 {
-                                state.cursor -= 1;
-                            }
-                        }
-                        KeyCode::Char(' ') => {
-                            let idx = state.cursor;
-                            state.selected[idx] = !state.selected[idx];
-                        }
-                        KeyCode::Enter => {
-                            let command = state.command.clone();
-                            let targets: Vec<usize> = state.selected.iter()
-                                .enumerate()
-                                .filter(|(_, &sel)| sel)
-                                .map(|(i, _)| i)
-                                .collect();
-                            app.input_mode = InputMode::Normal;
-                            for idx in targets {
-                                let server = app.servers[idx].clone();
-                                let mut args = server.to_ssh_args();
-                                // -t forces pseudo-terminal allocation for the remote command
-                                args.insert(0, "-t".to_string());
-                                args.push(command.clone());
-                                run_external_command(terminal, "ssh", &args)?;
-                            }
-                        }
-                        _ => {}
-                    },
-                },
-                InputMode::SelectingProfile => match key.code {
-                    KeyCode::Esc => app.input_mode = InputMode::Normal,
-                    KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => app.next_profile(),
-                    KeyCode::Up | KeyCode::Char('k') | KeyCode::BackTab => app.previous_profile(),
-                    KeyCode::Char('n') => {
-                        app.input_mode = InputMode::CreatingProfile(String::new());
+                    Err(e) => {
+                        app.input_mode = InputMode::ShowMessage(format!("'{}': {}", label, e));
                     }
-                    KeyCode::Enter => {
-                        if let Some(idx) = app.profile_state.selected() {
-                            let profile = app.profiles[idx].clone();
-                            let _ = app.load_profile(&profile);
+                }
+            }
+        }
+        Action::OpenTab => {
+            if let Some(idx) = app.state.selected() {
+                let server = app.servers[idx].clone();
+                let defaults = app.connection_defaults();
+                let command = if server.driver == Driver::Ssh {
+                    Ok((app.config.programs.ssh.clone(), server.to_ssh_args(&defaults)))
+                } else {
+                    server.driver.command(&server, &defaults, &app.config.programs)
+                };
+                match command {
+                    Ok((program, args)) => {
+                        if open_tab(terminal, app, &server.name, &program, &args)? {
+                            app.set_last_connected(&server);
                         }
-                        app.input_mode = InputMode::Normal;
                     }
-                    _ => {}
-                },
-                InputMode::CreatingProfile(name) => match key.code {
-                    KeyCode::Esc => app.input_mode = InputMode::SelectingProfile,
-                    KeyCode::Char(c) => name.push(c),
-                    KeyCode::Backspace => { name.pop(); }
-                    KeyCode::Enter => {
-                        if !name.is_empty() {
-                            let mut profile_name = name.clone();
-                            if !profile_name.ends_with(".json") {
-                                profile_name.push_str(".json");
-                            }
-                            if !app.profiles.contains(&profile_name) {
-                                app.profiles.push(profile_name.clone());
-                                app.profiles.sort();
-                            }
-                            let _ = app.load_profile(&profile_name);
-                            app.input_mode = InputMode::Normal;
-                        }
+                    Err(e) => {
+                        app.input_mode = InputMode::ShowMessage(format!("Cannot connect to '{}': {}", server.name, e));
                     }
-                    _ => {}
-                },
+                }
+            }
+        }
+        Action::Tabs => app.tab_prefix = true,
+        Action::Share => {
+            if let Some(index) = app.state.selected() {
+                // 只有 ssh 服务器能写成 URI
+                let format = if app.servers[index].driver.is_ssh() { ShareFormat::Uri } else { ShareFormat::Json };
+                app.input_mode = InputMode::Share { index, format };
+            }
+        }
+        Action::Import => {
+            app.input_mode = InputMode::Import {
+                text: String::new(),
+                error: None,
+            };
+        }
+        Action::QuickConnect => {
+            app.input_mode = InputMode::QuickConnect {
+                input: String::new(),
+                error: None,
+            };
+        }
+        Action::Tmux => {
+            if !app.servers.is_empty() {
+                app.input_mode = InputMode::TmuxSelect(TargetSelection::new(app.servers.len()));
+            }
+        }
+        Action::Tail => {
+            if !app.servers.is_empty() {
+                app.input_mode = InputMode::TailSetup(TailSetup::new(app.servers.len()));
+            }
+        }
+        Action::RunHistory => {
+            app.input_mode = InputMode::RunHistory {
+                runs: app.runs.load(),
+                cursor: 0,
+            };
+        }
+        Action::Broadcast => {
+            if !app.servers.is_empty() {
+                app.input_mode = InputMode::BroadcastCommand(
+                    BroadcastState::new(app.servers.len(), app.config.broadcast)
+                );
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Start a broadcast (or watch) in the background and switch to its results, or print it in dry-run mode.
+fn start_broadcast<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    template: &str,
+    targets: Vec<(usize, Vec<String>)>,
+    options: BroadcastOptions,
+    watch: bool,
+) -> io::Result<()> {
+    app.input_mode = InputMode::Normal;
+    if targets.is_empty() {
+        return Ok(());
+    }
+    if app.config.dry_run {
+        let commands: Vec<(String, Vec<String>)> = targets
+            .into_iter()
+            .map(|(_, args)| (app.config.programs.ssh.clone(), args))
+            .collect();
+        return print_commands(terminal, &app.config, &commands);
+    }
+    let _ = app.history.push(template);
+    let targets = targets
+        .into_iter()
+        .map(|(idx, args)| (app.servers[idx].name.clone(), args))
+        .collect();
+    let program = &app.config.programs.ssh;
+    app.input_mode = if watch {
+        let interval = Duration::from_secs(options.interval);
+        InputMode::Watch(WatchView::new(Watch::start(program, template, targets, interval)))
+    } else {
+        InputMode::BroadcastResults(ResultsView::new(Rollout::start(program, template, targets, options)))
+    };
+    Ok(())
+}
+
+/// Start following a file or journald unit on the chosen servers, or print the commands in dry-run mode.
+fn start_tail<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    source: TailSource,
+    target: &str,
+    indices: Vec<usize>,
+) -> io::Result<()> {
+    app.input_mode = InputMode::Normal;
+    if indices.is_empty() {
+        return Ok(());
+    }
+    // 路径中同样可以使用 {name} 等占位符
+    let defaults = app.connection_defaults();
+    let targets: Vec<(String, Vec<String>)> = indices
+        .into_iter()
+        .map(|i| {
+            let server = &app.servers[i];
+            let path = template::expand_known(target, &server.template_vars(&defaults));
+            (server.name.clone(), server.to_broadcast_args(&defaults, source.command(&path)))
+        })
+        .collect();
+    if app.config.dry_run {
+        let commands: Vec<(String, Vec<String>)> = targets
+            .into_iter()
+            .map(|(_, args)| (app.config.programs.ssh.clone(), args))
+            .collect();
+        return print_commands(terminal, &app.config, &commands);
+    }
+    let title = match source {
+        TailSource::File => target.to_string(),
+        TailSource::Unit => format!("journald unit {}", target),
+    };
+    let tail = Tail::start(&app.config.programs.ssh, &title, targets);
+    app.input_mode = InputMode::Tail(TailView::new(tail));
+    Ok(())
+}
+
+/// Open one tmux pane per server, tiled and with synchronized input.
+fn open_tmux_panes<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    indices: Vec<usize>,
+) -> io::Result<()> {
+    app.input_mode = InputMode::Normal;
+    if indices.is_empty() {
+        return Ok(());
+    }
+    let programs = &app.config.programs;
+    if !app.config.dry_run && !is_command_available(&programs.tmux) {
+        app.input_mode = InputMode::ShowMessage(format!("{} is not installed.", programs.tmux));
+        return Ok(());
+    }
+    let defaults = app.connection_defaults();
+    let panes: Vec<String> = indices
+        .iter()
+        .map(|&i| format_command(&programs.ssh, &app.servers[i].to_ssh_args(&defaults)))
+        .collect();
+    let stamp = file_stamp(&format_timestamp(unix_secs(SystemTime::now())));
+    let name = format!("sshx-{}", stamp);
+    let inside = tmux::inside_tmux();
+    let commands = tmux::pane_commands(&programs.tmux, &name, inside, &panes);
+    if app.config.dry_run {
+        return print_commands(terminal, &app.config, &commands);
+    }
+
+    // 先在后台建好窗格，再（在 tmux 外时）附加到新会话
+    let (program, args) = &commands[0];
+    if let Err(e) = run_captured(program, args) {
+        app.input_mode = InputMode::ShowMessage(format!("Could not open the tmux panes: {}", e));
+        return Ok(());
+    }
+    match commands.get(1) {
+        Some((program, args)) => run_external_command(terminal, &app.config, program, args),
+        None => {
+            app.input_mode = InputMode::ShowMessage(format!(
+                "Opened {} servers in tmux window {}.",
+                panes.len(),
+                name
+            ));
+            Ok(())
+        }
+    }
+}
+
+/// Start an interactive command according to `launch`, showing why if it could not be started.
+/// Returns false in that case.
+fn launch_connection<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    name: &str,
+    program: &str,
+    args: &[String],
+) -> io::Result<bool> {
+    match app.config.launch {
+        Launch::Tab => return open_tab(terminal, app, name, program, args),
+        // 打开的标签页会随 sshx 一起结束，此时退回到挂起 TUI
+        Launch::Exec if !app.config.dry_run && app.sessions.is_empty() => {
+            app.exec = Some((program.to_string(), args.to_vec()));
+            return Ok(true);
+        }
+        _ => {}
+    }
+    if let Err(e) = launch(terminal, &app.config, program, args)? {
+        app.input_mode = InputMode::ShowMessage(format!("Could not open a new {}: {}", app.config.launch.describe(), e));
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Connect to a server typed into the quick-connect bar, then offer to save it.
+fn quick_connect<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    server: Server,
+) -> io::Result<()> {
+    let defaults = app.connection_defaults();
+    let args = server.to_ssh_args(&defaults);
+    let program = app.config.programs.ssh.clone();
+    let launched = launch_connection(terminal, app, &server.name, &program, &args)?;
+
+    // 出错或 sshx 即将被替换时不再询问；已保存过的目标也不询问
+    let known = app.servers.iter().any(|s| {
+        s.driver.is_ssh()
+            && s.address(&defaults) == server.address(&defaults)
+            && s.effective_port(&defaults) == server.effective_port(&defaults)
+    });
+    if launched && app.exec.is_none() && !known {
+        app.input_mode = InputMode::ConfirmSave(server);
+    }
+    Ok(())
+}
+
+/// Run a command in a new tab inside sshx and switch to it; false if it could not be started.
+fn open_tab<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    name: &str,
+    program: &str,
+    args: &[String],
+) -> io::Result<bool> {
+    if app.config.dry_run {
+        print_commands(terminal, &app.config, &[(program.to_string(), args.to_vec())])?;
+        return Ok(true);
+    }
+    let area = ui::session_area(terminal.size()?);
+    match Session::start(name, program, args, area.height, area.width) {
+        Ok(session) => {
+            app.sessions.push(session);
+            app.active_tab = Some(app.sessions.len() - 1);
+            Ok(true)
+        }
+        Err(e) => {
+            app.input_mode = InputMode::ShowMessage(format!("Could not open a tab: {:#}", e));
+            Ok(false)
+        }
+    }
+}
+
+/// Keys pressed after the tab prefix.
+fn tab_command(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char(c @ '0'..='9') => app.show_tab(c as usize - '0' as usize),
+        KeyCode::Char('n') | KeyCode::Right | KeyCode::Tab => app.cycle_tab(true),
+        KeyCode::Char('p') | KeyCode::Left | KeyCode::BackTab => app.cycle_tab(false),
+        KeyCode::Char('x') => {
+            if let Some(i) = app.active_tab {
+                app.close_tab(i);
             }
         }
+        // 连按两次前缀键把它发送给会话
+        _ if app.keymap.is_key(Action::Tabs, key) => {
+            if let Some(i) = app.active_tab {
+                app.sessions[i].send_key(key);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Put `text` on the clipboard through the terminal and confirm it with a toast.
+fn copy_to_clipboard<B: Backend + std::io::Write>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    text: &str,
+    what: &str,
+) -> io::Result<()> {
+    match clipboard::copy(terminal.backend_mut(), text, &app.config.programs.tmux)? {
+        Ok(()) => app.show_toast(format!("Copied {}", what)),
+        Err(e) => app.input_mode = InputMode::ShowMessage(format!("Could not copy {}: {}", what, e)),
+    }
+    Ok(())
+}
+
+/// Show a message and return false when the server's driver does not speak ssh.
+fn require_ssh(app: &mut App, idx: usize, what: &str) -> bool {
+    let driver = app.servers[idx].driver;
+    if driver.is_ssh() {
+        return true;
+    }
+    app.input_mode = InputMode::ShowMessage(format!(
+        "{} is not supported by the {} driver.",
+        what,
+        driver.name()
+    ));
+    false
+}
+
+/// Save the profile; the mode to show next is the list, or the reason it was not saved.
+fn save_result(app: &App) -> InputMode {
+    match app.save() {
+        Ok(()) => InputMode::Normal,
+        Err(e) => InputMode::ShowMessage(format!("Failed to save the profile: {:#}", e)),
     }
 }
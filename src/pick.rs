@@ -0,0 +1,59 @@
+use serde_json::json;
+
+use crate::command::format_command;
+use crate::config::Programs;
+use crate::server::{ProfileDefaults, Server};
+use crate::template;
+
+/// What `sshx pick` prints for the chosen server.
+pub enum PickFormat {
+    /// The command line sshx would run to connect; other drivers print their own command.
+    Ssh,
+    /// `user@host`.
+    Target,
+    /// The server's connection details as a JSON object.
+    Json,
+    /// A template with the same placeholders as custom actions.
+    Template(String),
+}
+
+impl PickFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "ssh" => Ok(PickFormat::Ssh),
+            "target" => Ok(PickFormat::Target),
+            "json" => Ok(PickFormat::Json),
+            // 其余写法都当作模板，但至少要有一个占位符
+            template if template.contains('{') => Ok(PickFormat::Template(template.to_string())),
+            other => Err(format!(
+                "unknown format '{}'; use ssh, target, json or a template like '{{user}}@{{host}}'",
+                other
+            )),
+        }
+    }
+
+    pub fn render(&self, server: &Server, defaults: &ProfileDefaults, programs: &Programs) -> Result<String, String> {
+        match self {
+            PickFormat::Ssh => {
+                let (program, args) = server.driver.command(server, defaults, programs)?;
+                Ok(format_command(&program, &args))
+            }
+            PickFormat::Target => Ok(server.address(defaults)),
+            PickFormat::Json => {
+                let object = json!({
+                    "name": server.name,
+                    "driver": server.driver.name(),
+                    "user": server.effective_user(defaults),
+                    "host": server.host,
+                    "port": server.effective_port(defaults),
+                    "jump_host": server.effective_jump_host(defaults),
+                    "identity_file": server.effective_identity_file(defaults),
+                    "ssh_options": server.effective_ssh_options(defaults),
+                    "vars": server.vars,
+                });
+                Ok(object.to_string())
+            }
+            PickFormat::Template(format) => template::expand(format, &server.template_vars(defaults)),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::broadcast::{HostRun, HostStatus, Rollout};
+use crate::history::{read_json, write_json};
+
+/// Number of finished broadcasts kept in the `runs` directory.
+const RUN_LIMIT: usize = 100;
+
+/// A finished broadcast as written to report files and the run history.
+#[derive(Serialize, Deserialize)]
+pub struct Report {
+    pub command: String,
+    /// Start of the run, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub hosts: Vec<HostReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HostReport {
+    pub name: String,
+    /// `ok`, `failed`, `killed`, `error`, `skipped` or `running`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Seconds the command ran for.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+        }
+    }
+}
+
+impl Report {
+    pub fn from_rollout(rollout: &Rollout) -> Self {
+        let hosts = rollout
+            .hosts
+            .iter()
+            .map(|host| {
+                let (status, exit_code, error) = match &host.status {
+                    HostStatus::Exited(0) => ("ok", Some(0), None),
+                    HostStatus::Exited(code) => ("failed", Some(*code), None),
+                    HostStatus::Killed => ("killed", None, None),
+                    HostStatus::Error(e) => ("error", None, Some(e.clone())),
+                    HostStatus::Skipped => ("skipped", None, None),
+                    HostStatus::Pending | HostStatus::Running => ("running", None, None),
+                };
+                HostReport {
+                    name: host.name.clone(),
+                    status: status.to_string(),
+                    exit_code,
+                    error,
+                    duration: host.duration.map(|d| (d.as_secs_f64() * 1000.0).round() / 1000.0),
+                    stdout: host.stdout.clone(),
+                    stderr: host.stderr.clone(),
+                }
+            })
+            .collect();
+        Self {
+            command: rollout.command.clone(),
+            timestamp: rollout.timestamp.clone(),
+            hosts,
+        }
+    }
+
+    /// Show a past run in the results view.
+    pub fn to_rollout(&self) -> Rollout {
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|host| HostRun {
+                name: host.name.clone(),
+                args: Vec::new(),
+                status: Report::host_status(host),
+                stdout: host.stdout.clone(),
+                stderr: host.stderr.clone(),
+                started: None,
+                duration: host.duration.map(Duration::from_secs_f64),
+            })
+            .collect();
+        Rollout::finished(self.command.clone(), hosts, self.timestamp.clone())
+    }
+
+    /// Hosts that failed in any way.
+    pub fn failed(&self) -> usize {
+        self.hosts
+            .iter()
+            .filter(|h| matches!(h.status.as_str(), "failed" | "killed" | "error"))
+            .count()
+    }
+
+    fn host_status(host: &HostReport) -> HostStatus {
+        match (host.status.as_str(), host.exit_code) {
+            (_, Some(code)) => HostStatus::Exited(code),
+            ("killed", _) => HostStatus::Killed,
+            ("skipped", _) => HostStatus::Skipped,
+            _ => HostStatus::Error(host.error.clone().unwrap_or_else(|| host.status.clone())),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# Broadcast: `{}`\n", self.command);
+        let _ = writeln!(md, "- Time: {}", self.timestamp);
+        let _ = writeln!(md, "- Hosts: {} ({} failed)\n", self.hosts.len(), self.failed());
+
+        md.push_str("| Host | Status | Duration |\n|------|--------|----------|\n");
+        for host in &self.hosts {
+            let _ = writeln!(md, "| {} | {} | {} |", host.name, describe(host), duration(host));
+        }
+
+        for host in &self.hosts {
+            let _ = write!(md, "\n## {} ({})\n\n", host.name, describe(host));
+            md.push_str(&code_block(&host.stdout));
+            if !host.stderr.is_empty() {
+                md.push_str("\nstderr:\n\n");
+                md.push_str(&code_block(&host.stderr));
+            }
+        }
+        md
+    }
+
+    /// Write the report to `dir` and return the file's path.
+    pub fn export(&self, dir: &Path, format: ReportFormat) -> Result<PathBuf> {
+        let path = free_path(dir, &format!("sshx-{}", file_stamp(&self.timestamp)), format.extension());
+        match format {
+            ReportFormat::Json => write_json(&path, self)?,
+            ReportFormat::Markdown => fs::write(&path, self.to_markdown())?,
+        }
+        Ok(path)
+    }
+}
+
+fn describe(host: &HostReport) -> String {
+    match (&host.error, host.exit_code) {
+        (Some(e), _) => format!("error: {}", e),
+        (None, Some(code)) => format!("exit {}", code),
+        (None, None) => host.status.clone(),
+    }
+}
+
+fn duration(host: &HostReport) -> String {
+    host.duration.map(|d| format!("{:.1}s", d)).unwrap_or_default()
+}
+
+fn code_block(text: &str) -> String {
+    if text.is_empty() {
+        return "_no output_\n".to_string();
+    }
+    // 输出中本身含有 ``` 时用更长的围栏
+    let mut fence = "```".to_string();
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+    format!("{}text\n{}\n{}\n", fence, text.trim_end_matches('\n'), fence)
+}
+
+/// Finished broadcasts, one JSON report per run in the `runs` data directory.
+pub struct RunLog {
+    dir: PathBuf,
+}
+
+impl RunLog {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("runs"),
+        }
+    }
+
+    /// Record a run and drop the oldest ones beyond the limit.
+    pub fn save(&self, report: &Report) -> Result<()> {
+        let path = free_path(&self.dir, &file_stamp(&report.timestamp), "json");
+        write_json(&path, report)?;
+        let files = self.files();
+        for old in &files[..files.len().saturating_sub(RUN_LIMIT)] {
+            let _ = fs::remove_file(old);
+        }
+        Ok(())
+    }
+
+    /// Past runs, newest first; unreadable files are skipped.
+    pub fn load(&self) -> Vec<Report> {
+        self.files().iter().rev().filter_map(|path| read_json(path)).collect()
+    }
+
+    // 文件名就是时间戳，同一秒内的后续运行带 -2、-3 等后缀
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort_by_cached_key(|path| run_order(path));
+        files
+    }
+}
+
+/// Sort key of a run file: `20240501-123000-2.json` is (`20240501-123000`, 2) and comes after
+/// `20240501-123000.json`, the first run of that second. Sorting by name alone would put `-2`
+/// first, and `-10` before `-2`.
+fn run_order(path: &Path) -> (String, u32) {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    match stem.rsplit_once('-') {
+        // 时间戳本身含一个 '-'，第二个 '-' 之后才是序号
+        Some((stamp, n)) if stamp.contains('-') => (stamp.to_string(), n.parse().unwrap_or(0)),
+        _ => (stem.to_string(), 1),
+    }
+}
+
+/// `dir/stem.ext`, or `dir/stem-2.ext` and so on when the file already exists.
+pub fn free_path(dir: &Path, stem: &str, ext: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, ext));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, n, ext));
+        n += 1;
+    }
+    path
+}
+
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// `2024-05-01T12:30:00Z` for seconds since the Unix epoch.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // 公历日期换算（Howard Hinnant 的 civil_from_days）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// `20240501-123000` from a timestamp written by `format_timestamp`.
+pub fn file_stamp(timestamp: &str) -> String {
+    timestamp
+        .chars()
+        .filter_map(|c| match c {
+            'T' => Some('-'),
+            '0'..='9' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,82 +1,429 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+use crate::driver::Driver;
+use crate::template;
+use crate::validate;
+
+pub const DEFAULT_PORT: u16 = 22;
+pub const DEFAULT_USER: &str = "root";
+pub const DEFAULT_BAUD: u32 = 115200;
+pub const DEFAULT_SHELL: &str = "sh";
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Server {
     pub name: String,
-    pub user: String,
+    #[serde(default, skip_serializing_if = "is_default_driver")]
+    pub driver: Driver,
+    #[serde(default)]
+    pub user: String, // 为空时继承 profile 默认值
+    #[serde(default)]
     pub host: String,
-    pub port: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_port",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub port: Option<u16>, // None 时继承 profile 默认值
     #[serde(default)]
-    pub jump_host: String, // 跳板机，格式: user@host:port 或 user@host
+    pub jump_host: String, // 跳板机，格式: user@host:port 或 user@host，"none" 表示不使用默认跳板机
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub identity_file: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ssh_options: Vec<String>, // 以 -o 传递给 ssh，格式: Key=Value
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String, // 串口设备、容器名或 Pod 名，取决于 driver
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub namespace: String, // kubectl
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baud: Option<u32>, // serial
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String, // docker/kubectl 的 shell，或 custom driver 的命令模板
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>, // 命令模板中可用的自定义变量
+}
+
+fn is_default_driver(driver: &Driver) -> bool {
+    *driver == Driver::Ssh
+}
+
+/// Values shared by every server in a profile unless the server overrides them.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProfileDefaults {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_port",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub jump_host: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub identity_file: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ssh_options: Vec<String>,
+}
+
+/// Accept both the numeric port and the string form written by older versions.
+fn deserialize_port<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawPort {
+        Number(i64),
+        Text(String),
+    }
+
+    let port = match Option::<RawPort>::deserialize(deserializer)? {
+        Some(RawPort::Number(port)) => port.to_string(),
+        // 旧配置中的端口是字符串；空字符串表示未设置
+        Some(RawPort::Text(text)) if text.trim().is_empty() => return Ok(None),
+        Some(RawPort::Text(text)) => text.trim().to_string(),
+        None => return Ok(None),
+    };
+    // 数字和字符串按同样的规则检查，非法端口报错而不是悄悄用默认端口
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(Some(port)),
+        _ => Err(serde::de::Error::custom(format!("'{}' is not a port between 1 and 65535", port))),
+    }
 }
 
 impl Server {
-    pub fn address(&self) -> String {
-        format!("{}@{}", self.user, self.host)
+    /// An unsaved ssh server from `[user@]host[:port]` or `ssh://[user@]host[:port]`,
+    /// named after its host.
+    pub fn from_target(target: &str) -> Result<Server, String> {
+        let rest = match target.split_once("://") {
+            Some(("ssh", rest)) => rest.strip_suffix('/').unwrap_or(rest),
+            Some((scheme, _)) => return Err(format!("Unsupported scheme '{}://'", scheme)),
+            None => target,
+        };
+        let (user, host_port) = match rest.rsplit_once('@') {
+            // URI 的用户部分可能带有 ;fingerprint= 等参数，ssh 不需要
+            Some((user, host_port)) => (user.split(';').next().unwrap_or(user), host_port),
+            None => ("", rest),
+        };
+        if user.is_empty() && rest.contains('@') {
+            return Err(format!("Invalid user in '{}'", target));
+        }
+        validate::check_user(user)?;
+        let (host, port) = validate::split_host_port(host_port);
+        validate::check_host(host)?;
+        let port = port.map(validate::parse_port).transpose()?;
+        // ssh 的目标参数不接受带方括号的 IPv6 地址
+        let host = validate::unbracket(host);
+        Ok(Server {
+            name: host.to_string(),
+            driver: Driver::Ssh,
+            user: user.to_string(),
+            host: host.to_string(),
+            port,
+            jump_host: String::new(),
+            identity_file: String::new(),
+            ssh_options: Vec::new(),
+            target: String::new(),
+            namespace: String::new(),
+            baud: None,
+            command: String::new(),
+            vars: BTreeMap::new(),
+        })
+    }
+
+    /// A target as typed into the quick-connect bar: `from_target` plus an optional
+    /// `-J jump` before or after it.
+    pub fn parse_quick(input: &str) -> Result<Server, String> {
+        let words = shell_words::split(input).map_err(|e| format!("Invalid quoting: {}", e))?;
+        let mut words = words.into_iter();
+        let mut target = None;
+        let mut jump_host = String::new();
+        while let Some(word) = words.next() {
+            if word == "-J" {
+                jump_host = words.next().ok_or("-J needs a jump host")?;
+            } else if let Some(jump) = word.strip_prefix("-J") {
+                jump_host = jump.to_string();
+            } else if target.is_none() {
+                target = Some(word);
+            } else {
+                return Err(format!("Unexpected '{}'", word));
+            }
+        }
+        let target = target.ok_or("Enter a target like user@host:port")?;
+        validate::check_jump_host(&jump_host)?;
+        let mut server = Server::from_target(&target)?;
+        server.jump_host = jump_host;
+        Ok(server)
+    }
+
+    pub fn effective_user<'a>(&'a self, defaults: &'a ProfileDefaults) -> &'a str {
+        if !self.user.is_empty() {
+            &self.user
+        } else if !defaults.user.is_empty() {
+            &defaults.user
+        } else {
+            DEFAULT_USER
+        }
+    }
+
+    pub fn effective_port(&self, defaults: &ProfileDefaults) -> u16 {
+        match self.driver {
+            // 非 ssh 协议的端口不继承 profile 默认值
+            Driver::Et | Driver::Telnet => self.port.or(self.driver.default_port()).unwrap_or(DEFAULT_PORT),
+            _ => self.port.or(defaults.port).unwrap_or(DEFAULT_PORT),
+        }
+    }
+
+    pub fn effective_jump_host<'a>(&'a self, defaults: &'a ProfileDefaults) -> Option<&'a str> {
+        let jump_host = if self.jump_host.is_empty() {
+            &defaults.jump_host
+        } else {
+            &self.jump_host
+        };
+        if jump_host.is_empty() || jump_host == "none" {
+            None
+        } else {
+            Some(jump_host)
+        }
+    }
+
+    pub fn effective_identity_file<'a>(&'a self, defaults: &'a ProfileDefaults) -> Option<&'a str> {
+        if !self.identity_file.is_empty() {
+            Some(&self.identity_file)
+        } else if !defaults.identity_file.is_empty() {
+            Some(&defaults.identity_file)
+        } else {
+            None
+        }
     }
 
-    pub fn to_ssh_args(&self) -> Vec<String> {
+    /// Server options come first: ssh uses the first value it sees for each option.
+    pub fn effective_ssh_options<'a>(&'a self, defaults: &'a ProfileDefaults) -> Vec<&'a str> {
+        self.ssh_options
+            .iter()
+            .chain(defaults.ssh_options.iter())
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn address(&self, defaults: &ProfileDefaults) -> String {
+        format!("{}@{}", self.effective_user(defaults), self.host)
+    }
+
+    /// `-i` and `-o` arguments understood by ssh, sftp and ssh-copy-id alike.
+    fn identity_and_option_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
         let mut args = vec![];
-        // 添加跳板机参数
-        if !self.jump_host.is_empty() {
-            args.push("-J".to_string());
-            args.push(self.jump_host.clone());
+        if let Some(identity_file) = self.effective_identity_file(defaults) {
+            args.push("-i".to_string());
+            args.push(identity_file.to_string());
         }
-        args.push(self.address());
-        if !self.port.is_empty() {
-            args.push("-p".to_string());
-            args.push(self.port.clone());
+        for option in self.effective_ssh_options(defaults) {
+            args.push("-o".to_string());
+            args.push(option.to_string());
         }
         args
     }
 
-    pub fn to_mosh_args(&self) -> Vec<String> {
+    pub fn to_ssh_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
         let mut args = vec![];
-        let mut ssh_opts = String::new();
-        if !self.port.is_empty() {
-            ssh_opts.push_str(&format!("-p {}", self.port));
-        }
-        if !self.jump_host.is_empty() {
-            if !ssh_opts.is_empty() {
-                ssh_opts.push(' ');
-            }
-            ssh_opts.push_str(&format!("-J {}", self.jump_host));
+        // 添加跳板机参数
+        if let Some(jump_host) = self.effective_jump_host(defaults) {
+            args.push("-J".to_string());
+            args.push(jump_host.to_string());
         }
-        if !ssh_opts.is_empty() {
-            args.push("--ssh".to_string());
-            args.push(format!("ssh {}", ssh_opts));
+        args.extend(self.identity_and_option_args(defaults));
+        args.push("-p".to_string());
+        args.push(self.effective_port(defaults).to_string());
+        // 目标之前加 --，即使用户名或主机以 - 开头也不会被当作选项
+        args.push("--".to_string());
+        args.push(self.address(defaults));
+        args
+    }
+
+    /// ssh arguments that run `command` on the server with its output captured.
+    pub fn to_broadcast_args(&self, defaults: &ProfileDefaults, command: String) -> Vec<String> {
+        let mut args = self.to_ssh_args(defaults);
+        // 输出在后台收集，不能交互输入密码；BatchMode 让 ssh 直接失败而不是在 TUI 上提示
+        args.insert(0, "-o".to_string());
+        args.insert(1, "BatchMode=yes".to_string());
+        args.push(command);
+        args
+    }
+
+    /// Arguments for mosh; `ssh` is the program mosh runs to start the server.
+    pub fn to_mosh_args(&self, defaults: &ProfileDefaults, ssh: &str) -> Vec<String> {
+        let mut args = vec![];
+        let mut ssh_command = vec![ssh.to_string(), "-p".to_string(), self.effective_port(defaults).to_string()];
+        if let Some(jump_host) = self.effective_jump_host(defaults) {
+            ssh_command.push("-J".to_string());
+            ssh_command.push(jump_host.to_string());
         }
-        args.push(self.address());
+        ssh_command.extend(self.identity_and_option_args(defaults));
+        // mosh 按 shell 规则拆分 --ssh，路径和选项中的空格需要引起来
+        args.push("--ssh".to_string());
+        args.push(shell_words::join(&ssh_command));
+        args.push("--".to_string());
+        args.push(self.address(defaults));
         args
     }
 
-    pub fn to_copy_id_args(&self) -> Vec<String> {
+    pub fn to_copy_id_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
         let mut args = vec![];
         // 添加跳板机参数
-        if !self.jump_host.is_empty() {
+        if let Some(jump_host) = self.effective_jump_host(defaults) {
             args.push("-o".to_string());
-            args.push(format!("ProxyJump={}", self.jump_host));
-        }
-        if !self.port.is_empty() {
-            args.push("-p".to_string());
-            args.push(self.port.clone());
+            args.push(format!("ProxyJump={}", jump_host));
         }
-        args.push(self.address());
+        args.extend(self.identity_and_option_args(defaults));
+        args.push("-p".to_string());
+        args.push(self.effective_port(defaults).to_string());
+        args.push("--".to_string());
+        args.push(self.address(defaults));
         args
     }
 
-    pub fn to_sftp_args(&self) -> Vec<String> {
+    pub fn to_sftp_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
         let mut args = vec![];
-        if !self.jump_host.is_empty() {
+        if let Some(jump_host) = self.effective_jump_host(defaults) {
             args.push("-J".to_string());
-            args.push(self.jump_host.clone());
+            args.push(jump_host.to_string());
         }
-        if !self.port.is_empty() {
-            args.push("-P".to_string());
-            args.push(self.port.clone());
+        args.extend(self.identity_and_option_args(defaults));
+        args.push("-P".to_string());
+        args.push(self.effective_port(defaults).to_string());
+        args.push("--".to_string());
+        args.push(self.address(defaults));
+        args
+    }
+
+    pub fn to_autossh_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
+        // -M 0 关闭 autossh 的监控端口，依赖 ssh 自身的 ServerAlive 检测
+        let mut args = vec!["-M".to_string(), "0".to_string()];
+        args.extend(self.to_ssh_args(defaults));
+        args
+    }
+
+    /// et takes a single jump host, and its port as `--jport` rather than `host:port`.
+    pub fn to_et_args(&self, defaults: &ProfileDefaults) -> Result<Vec<String>, String> {
+        let mut args = vec![];
+        if let Some(jump_host) = self.effective_jump_host(defaults) {
+            if jump_host.contains(',') {
+                return Err("et supports only one jump host".to_string());
+            }
+            let (user, host_port) = match jump_host.trim().rsplit_once('@') {
+                Some((user, rest)) => (Some(user), rest),
+                None => (None, jump_host.trim()),
+            };
+            let (host, jump_port) = validate::split_host_port(host_port);
+            args.push("--jumphost".to_string());
+            args.push(match user {
+                Some(user) => format!("{}@{}", user, host),
+                None => host.to_string(),
+            });
+            if let Some(port) = jump_port {
+                args.push("--jport".to_string());
+                args.push(port.to_string());
+            }
         }
-        args.push(self.address());
+        args.push("--".to_string());
+        args.push(format!("{}:{}", self.address(defaults), self.effective_port(defaults)));
+        Ok(args)
+    }
+
+    pub fn to_telnet_args(&self, defaults: &ProfileDefaults) -> Vec<String> {
+        let mut args = vec![];
+        if !self.user.is_empty() {
+            args.push("-l".to_string());
+            args.push(self.user.clone());
+        }
+        args.push(self.host.clone());
+        args.push(self.effective_port(defaults).to_string());
         args
     }
-}
 
+    pub fn to_picocom_args(&self) -> Vec<String> {
+        vec![
+            "-b".to_string(),
+            self.baud.unwrap_or(DEFAULT_BAUD).to_string(),
+            self.target.clone(),
+        ]
+    }
+
+    pub fn to_docker_args(&self) -> Result<Vec<String>, String> {
+        let mut args = vec!["exec".to_string(), "-it".to_string()];
+        if !self.user.is_empty() {
+            args.push("-u".to_string());
+            args.push(self.user.clone());
+        }
+        args.push(self.target.clone());
+        args.extend(self.shell()?);
+        Ok(args)
+    }
+
+    pub fn to_kubectl_args(&self) -> Result<Vec<String>, String> {
+        let mut args = vec!["exec".to_string(), "-it".to_string()];
+        if !self.namespace.is_empty() {
+            args.push("-n".to_string());
+            args.push(self.namespace.clone());
+        }
+        args.push(self.target.clone());
+        args.push("--".to_string());
+        args.extend(self.shell()?);
+        Ok(args)
+    }
+
+    /// The custom driver's command template with placeholders expanded.
+    pub fn to_custom_args(&self, defaults: &ProfileDefaults) -> Result<Vec<String>, String> {
+        template::expand_words(&self.command, &self.template_vars(defaults))
+    }
+
+    /// Words of the command docker and kubectl run in the container, e.g. `bash -l`.
+    fn shell(&self) -> Result<Vec<String>, String> {
+        if self.command.is_empty() {
+            return Ok(vec![DEFAULT_SHELL.to_string()]);
+        }
+        shell_words::split(&self.command).map_err(|e| format!("Invalid quoting: {}", e))
+    }
+
+    /// Values available to command templates: `{name}`, `{user}`, `{host}`, `{port}`, `{jump}`
+    /// and the server's own variables.
+    pub fn template_vars<'a>(&'a self, defaults: &ProfileDefaults) -> Vec<(&'a str, String)> {
+        let mut vars = vec![
+            ("name", self.name.clone()),
+            ("user", self.effective_user(defaults).to_string()),
+            ("host", self.host.clone()),
+            ("port", self.effective_port(defaults).to_string()),
+            ("jump", self.effective_jump_host(defaults).unwrap_or("").to_string()),
+        ];
+        vars.extend(self.vars.iter().map(|(key, value)| (key.as_str(), value.clone())));
+        vars
+    }
+
+    /// Short description shown next to the server name in lists.
+    pub fn describe(&self, defaults: &ProfileDefaults) -> String {
+        match self.driver {
+            Driver::Ssh => format!(
+                "({}) - {}:{}",
+                self.effective_user(defaults),
+                self.host,
+                self.effective_port(defaults)
+            ),
+            Driver::Autossh | Driver::Et | Driver::Telnet | Driver::Custom => format!(
+                "[{}] ({}) - {}:{}",
+                self.driver.name(),
+                self.effective_user(defaults),
+                self.host,
+                self.effective_port(defaults)
+            ),
+            Driver::Serial => format!(
+                "[serial] {} @ {}",
+                self.target,
+                self.baud.unwrap_or(DEFAULT_BAUD)
+            ),
+            Driver::Docker => format!("[docker] {}", self.target),
+            Driver::Kubectl if self.namespace.is_empty() => format!("[kubectl] {}", self.target),
+            Driver::Kubectl => format!("[kubectl] {}/{}", self.namespace, self.target),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::{
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+/// Lines kept above the visible screen of a tab.
+const SCROLLBACK: usize = 5000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SessionStatus {
+    Running,
+    Exited(u32),
+}
+
+/// An interactive command running in a pseudo-terminal, shown as a tab inside sshx.
+pub struct Session {
+    pub name: String,
+    pub status: SessionStatus,
+    /// Lines scrolled back into the history; 0 shows the live screen.
+    pub scroll: usize,
+    parser: Arc<Mutex<vt100::Parser>>,
+    updated: Arc<AtomicBool>, // 上次 poll 之后有新输出
+    size: (u16, u16),
+    writer: Box<dyn Write + Send>,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+impl Session {
+    /// Start `program` in a new pseudo-terminal of `rows` x `cols`.
+    pub fn start(name: &str, program: &str, args: &[String], rows: u16, cols: u16) -> Result<Self> {
+        let size = (rows.max(1), cols.max(1));
+        let pair = native_pty_system()
+            .openpty(pty_size(size))
+            .map_err(|e| anyhow!("failed to open a pseudo-terminal: {}", e))?;
+
+        let mut command = CommandBuilder::new(program);
+        command.args(args);
+        // 屏幕由 vt100 解析，按 xterm 的能力声明终端类型
+        command.env("TERM", "xterm-256color");
+        if let Ok(dir) = std::env::current_dir() {
+            command.cwd(dir);
+        }
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| anyhow!("failed to run {}: {}", program, e))?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| anyhow!(e.to_string()))?;
+        let writer = pair.master.take_writer().map_err(|e| anyhow!(e.to_string()))?;
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(size.0, size.1, SCROLLBACK)));
+        let output = Arc::clone(&parser);
+        let updated = Arc::new(AtomicBool::new(true));
+        let notify = Arc::clone(&updated);
+        thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                output.lock().unwrap_or_else(|e| e.into_inner()).process(&buf[..n]);
+                notify.store(true, Ordering::Relaxed);
+            }
+        });
+
+        Ok(Self {
+            name: name.to_string(),
+            status: SessionStatus::Running,
+            scroll: 0,
+            parser,
+            updated,
+            size,
+            writer,
+            master: pair.master,
+            child,
+        })
+    }
+
+    /// Notice when the command has ended; true if the screen changed since the last call.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = self.updated.swap(false, Ordering::Relaxed);
+        if self.status == SessionStatus::Running {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.status = SessionStatus::Exited(status.exit_code());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// The terminal screen; holds a lock that the output reader waits on.
+    pub fn screen(&self) -> MutexGuard<'_, vt100::Parser> {
+        self.parser.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Follow a change of the area the tab is drawn in.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        let size = (rows.max(1), cols.max(1));
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.screen().set_size(size.0, size.1);
+        let _ = self.master.resize(pty_size(size));
+    }
+
+    /// Move through the scrollback; positive values go back in time.
+    pub fn scroll_by(&mut self, lines: isize) {
+        let wanted = self.scroll.saturating_add_signed(lines);
+        let mut parser = self.screen();
+        parser.set_scrollback(wanted);
+        // vt100 限制在实际保存的行数内
+        let scroll = parser.screen().scrollback();
+        drop(parser);
+        self.scroll = scroll;
+    }
+
+    /// Type a key into the session.
+    pub fn send_key(&mut self, key: KeyEvent) {
+        let application_cursor = self.screen().screen().application_cursor();
+        let bytes = key_bytes(key, application_cursor);
+        self.send(&bytes);
+    }
+
+    pub fn send(&mut self, bytes: &[u8]) {
+        if self.scroll > 0 {
+            self.scroll_by(-(self.scroll as isize));
+        }
+        if self.writer.write_all(bytes).is_ok() {
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.status == SessionStatus::Running {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+fn pty_size((rows, cols): (u16, u16)) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// Bytes an xterm sends for a key press.
+fn key_bytes(key: KeyEvent, application_cursor: bool) -> Vec<u8> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    // 方向键在应用光标模式（如 vim、less）下使用 SS3 序列
+    let cursor = |c: char| {
+        if application_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        }
+    };
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if ctrl => match c.to_ascii_lowercase() {
+            c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+            ' ' | '@' | '2' => vec![0],
+            '[' | '3' => vec![0x1b],
+            '\\' | '4' => vec![0x1c],
+            ']' | '5' => vec![0x1d],
+            '^' | '6' => vec![0x1e],
+            '_' | '/' | '7' => vec![0x1f],
+            c => c.to_string().into_bytes(),
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor('A'),
+        KeyCode::Down => cursor('B'),
+        KeyCode::Right => cursor('C'),
+        KeyCode::Left => cursor('D'),
+        KeyCode::Home => cursor('H'),
+        KeyCode::End => cursor('F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char).into_bytes(),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][usize::from(n - 5)];
+            format!("\x1b[{}~", code).into_bytes()
+        }
+        _ => Vec::new(),
+    };
+    if alt && !bytes.is_empty() {
+        bytes.insert(0, 0x1b);
+    }
+    bytes
+}
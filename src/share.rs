@@ -0,0 +1,162 @@
+use serde::Deserialize;
+
+use crate::app::FormField;
+use crate::driver::Driver;
+use crate::server::{ProfileDefaults, Server};
+use crate::validate;
+
+/// ssh options that run a local or remote command; a pasted snippet must not bring them in.
+const COMMAND_OPTIONS: [&str; 5] = [
+    "ProxyCommand",
+    "LocalCommand",
+    "PermitLocalCommand",
+    "RemoteCommand",
+    "KnownHostsCommand",
+];
+
+/// How a server is written for sharing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ShareFormat {
+    Uri,
+    Json,
+    Toml,
+}
+
+impl ShareFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            ShareFormat::Uri => "ssh:// URI",
+            ShareFormat::Json => "JSON",
+            ShareFormat::Toml => "TOML",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ShareFormat::Uri => ShareFormat::Json,
+            ShareFormat::Json => ShareFormat::Toml,
+            ShareFormat::Toml => ShareFormat::Uri,
+        }
+    }
+}
+
+/// The server as text a teammate can paste into their own sshx. Values inherited from the
+/// profile are written out, since the other side does not have the same defaults.
+pub fn export(server: &Server, defaults: &ProfileDefaults, format: ShareFormat) -> Result<String, String> {
+    let server = resolved(server, defaults);
+    match format {
+        ShareFormat::Uri if !server.driver.is_ssh() => {
+            Err(format!("The {} driver has no ssh:// form", server.driver.name()))
+        }
+        ShareFormat::Uri => {
+            // URI 中的 IPv6 地址需要方括号
+            let host = if server.host.contains(':') {
+                format!("[{}]", server.host)
+            } else {
+                server.host.clone()
+            };
+            Ok(format!("ssh://{}@{}:{}", server.user, host, server.port.unwrap_or_default()))
+        }
+        ShareFormat::Json => serde_json::to_string_pretty(&server).map_err(|e| e.to_string()),
+        ShareFormat::Toml => toml::to_string(&server).map_err(|e| e.to_string()),
+    }
+}
+
+fn resolved(server: &Server, defaults: &ProfileDefaults) -> Server {
+    let mut shared = server.clone();
+    // 串口、容器等没有网络地址的 driver 不继承用户名和端口
+    if server.driver.default_port().is_some() {
+        shared.user = server.effective_user(defaults).to_string();
+        shared.port = Some(server.effective_port(defaults));
+    }
+    if server.driver.is_ssh() {
+        // "none" 要保留，否则对方会用上自己的默认跳板机
+        shared.jump_host = server.effective_jump_host(defaults).unwrap_or(&server.jump_host).to_string();
+        shared.identity_file = server.effective_identity_file(defaults).unwrap_or("").to_string();
+        shared.ssh_options = server.effective_ssh_options(defaults).into_iter().map(String::from).collect();
+    }
+    shared
+}
+
+/// Servers from pasted text: `ssh://` URIs (one per line), a JSON object or array, or a TOML
+/// table or `[[servers]]` array.
+pub fn import(text: &str) -> Result<Vec<Server>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Nothing to import".to_string());
+    }
+    let mut servers = if text.starts_with("ssh://") {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Server::from_target)
+            .collect::<Result<Vec<_>, _>>()?
+    } else if text.starts_with('{') || (text.starts_with('[') && !text.starts_with("[[")) {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(Box<Server>),
+            Many(Vec<Server>),
+        }
+        match serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))? {
+            OneOrMany::One(server) => vec![*server],
+            OneOrMany::Many(servers) => servers,
+        }
+    } else {
+        #[derive(Deserialize)]
+        struct Servers {
+            servers: Vec<Server>,
+        }
+        // 先按 [[servers]] 数组解析，否则整个片段就是一台服务器
+        match toml::from_str::<Servers>(text) {
+            Ok(list) => list.servers,
+            Err(_) => vec![toml::from_str::<Server>(text).map_err(|e| format!("Invalid TOML: {}", e.message()))?],
+        }
+    };
+    for server in &mut servers {
+        check(server).map_err(|e| format!("{}: {}", server.name, e))?;
+        server.host = validate::unbracket(&server.host).to_string();
+    }
+    Ok(servers)
+}
+
+fn check(server: &Server) -> Result<(), String> {
+    if server.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    // 片段可能来自别人：会执行命令的 driver 和 ssh 选项一律拒绝，其余按表单的规则检查
+    if server.driver == Driver::Custom {
+        return Err("servers with the custom driver run their own command and cannot be imported".to_string());
+    }
+    for &field in server.driver.required() {
+        let value = match field {
+            FormField::Host => &server.host,
+            FormField::Target => &server.target,
+            FormField::Command => &server.command,
+            _ => continue,
+        };
+        if value.trim().is_empty() {
+            return Err(format!("{} is required", field.label(server.driver)));
+        }
+    }
+    for option in &server.ssh_options {
+        validate::check_ssh_option(option)?;
+        let key = option.split('=').next().unwrap_or_default();
+        if COMMAND_OPTIONS.iter().any(|o| o.eq_ignore_ascii_case(key.trim())) {
+            return Err(format!("the ssh option {} runs a command and cannot be imported", key));
+        }
+    }
+    for (field, value) in [(FormField::Target, &server.target), (FormField::Namespace, &server.namespace)] {
+        if value.starts_with('-') {
+            return Err(format!("{} must not start with '-'", field.label(server.driver)));
+        }
+    }
+    validate::check_user(&server.user)?;
+    if !server.host.is_empty() {
+        validate::check_host(&server.host)?;
+    }
+    if server.jump_host != "none" {
+        validate::check_jump_host(&server.jump_host)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+/// Lines kept in memory; older ones are dropped.
+const LINE_LIMIT: usize = 10_000;
+
+/// Where the lines of a tail come from on every server.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TailSource {
+    File,
+    Unit,
+}
+
+impl TailSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            TailSource::File => "File",
+            TailSource::Unit => "journald unit",
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            TailSource::File => TailSource::Unit,
+            TailSource::Unit => TailSource::File,
+        }
+    }
+
+    /// Remote command that follows `target`, starting with its last lines.
+    pub fn command(self, target: &str) -> String {
+        let target = shell_words::quote(target);
+        match self {
+            TailSource::File => format!("tail -n 20 -F -- {}", target),
+            TailSource::Unit => format!("journalctl --no-pager -n 20 -f -u {}", target),
+        }
+    }
+}
+
+pub enum TailHostState {
+    Running,
+    Exited(Option<i32>), // None: 被信号终止
+    Error(String),
+}
+
+pub struct TailHost {
+    pub name: String,
+    pub state: TailHostState,
+}
+
+pub struct TailLine {
+    pub host: usize,
+    pub text: String,
+    pub stderr: bool,
+}
+
+/// Long-running commands on several servers whose output is merged into one stream.
+pub struct Tail {
+    pub source: String,
+    pub hosts: Vec<TailHost>,
+    pub lines: VecDeque<TailLine>,
+    pub paused: bool,
+    held: VecDeque<TailLine>, // 暂停期间收到的行，恢复时再追加
+    dropped: usize,           // 暂停期间超出上限而丢弃的行数
+    children: Vec<Option<Child>>,
+    rx: Receiver<TailLine>,
+}
+
+impl Tail {
+    /// Start following on every target; `targets` are (server name, program arguments).
+    pub fn start(program: &str, source: &str, targets: Vec<(String, Vec<String>)>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let mut hosts = Vec::new();
+        let mut children = Vec::new();
+        for (i, (name, args)) in targets.into_iter().enumerate() {
+            let child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn();
+            match child {
+                Ok(mut child) => {
+                    if let Some(stdout) = child.stdout.take() {
+                        read_lines(stdout, i, false, tx.clone());
+                    }
+                    if let Some(stderr) = child.stderr.take() {
+                        read_lines(stderr, i, true, tx.clone());
+                    }
+                    hosts.push(TailHost { name, state: TailHostState::Running });
+                    children.push(Some(child));
+                }
+                Err(e) => {
+                    let state = TailHostState::Error(format!("failed to run {}: {}", program, e));
+                    hosts.push(TailHost { name, state });
+                    children.push(None);
+                }
+            }
+        }
+        Self {
+            source: source.to_string(),
+            hosts,
+            lines: VecDeque::new(),
+            paused: false,
+            held: VecDeque::new(),
+            dropped: 0,
+            children,
+            rx,
+        }
+    }
+
+    /// Collect new lines and notice servers whose command ended.
+    pub fn poll(&mut self) {
+        while let Ok(line) = self.rx.try_recv() {
+            if self.paused {
+                if self.held.len() >= LINE_LIMIT {
+                    self.held.pop_front();
+                    self.dropped += 1;
+                }
+                self.held.push_back(line);
+            } else {
+                self.push(line);
+            }
+        }
+        for (host, child) in self.hosts.iter_mut().zip(&mut self.children) {
+            let Some(process) = child else { continue };
+            match process.try_wait() {
+                Ok(Some(status)) => {
+                    host.state = TailHostState::Exited(status.code());
+                    *child = None;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    host.state = TailHostState::Error(e.to_string());
+                    *child = None;
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, line: TailLine) {
+        if self.lines.len() >= LINE_LIMIT {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Freeze the stream, or add everything received since it was frozen.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            for line in std::mem::take(&mut self.held) {
+                self.push(line);
+            }
+            self.dropped = 0;
+        }
+    }
+
+    /// Lines received while paused and not shown yet.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Lines received while paused that were dropped because more than `LINE_LIMIT` came in.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl Drop for Tail {
+    fn drop(&mut self) {
+        for child in self.children.iter_mut().flatten() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_lines<R: Read + Send + 'static>(reader: R, host: usize, stderr: bool, tx: Sender<TailLine>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        // 按字节读取，日志中的非 UTF-8 内容不会中断读取
+        while matches!(reader.read_until(b'\n', &mut buf), Ok(n) if n > 0) {
+            let text = String::from_utf8_lossy(&buf).trim_end_matches(['\n', '\r']).to_string();
+            buf.clear();
+            if tx.send(TailLine { host, text, stderr }).is_err() {
+                break;
+            }
+        }
+    });
+}
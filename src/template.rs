@@ -0,0 +1,102 @@
+/// Placeholders every server provides; custom server variables may not reuse these names.
+pub const SERVER_PLACEHOLDERS: [&str; 5] = ["name", "user", "host", "port", "jump"];
+
+/// Replace `{placeholder}`s with their values; `{{` and `}}` produce literal braces.
+pub fn expand(template: &str, vars: &[(&str, String)]) -> Result<String, String> {
+    expand_with(template, |name| {
+        vars.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| format!("unknown placeholder '{{{}}}'", name))
+    })
+}
+
+/// Check a command template's syntax without knowing which placeholders will exist.
+pub fn check(template: &str) -> Result<(), String> {
+    for word in split(template)? {
+        expand_with(&word, |_| Ok(String::new()))?;
+    }
+    Ok(())
+}
+
+fn expand_with(
+    template: &str,
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unterminated placeholder '{{{}'", name)),
+                    }
+                }
+                out.push_str(&lookup(&name)?);
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Replace only the `{placeholder}`s that have a value and leave every other brace alone,
+/// so shell syntax such as `${HOME}`, `find -exec {}` or `awk '{print $1}'` passes through.
+pub fn expand_known(template: &str, vars: &[(&str, String)]) -> String {
+    expand_known_with(template, vars, |value| value.to_string())
+}
+
+/// `expand_known` for a shell command line: each value is quoted, so a host name or
+/// variable can only ever be a single word and never shell syntax.
+pub fn expand_command(template: &str, vars: &[(&str, String)]) -> String {
+    expand_known_with(template, vars, |value| shell_words::quote(value).into_owned())
+}
+
+fn expand_known_with(template: &str, vars: &[(&str, String)], insert: impl Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(&insert(value));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split a command template into words like a POSIX shell, then expand each word,
+/// so values containing spaces stay a single argument.
+pub fn expand_words(template: &str, vars: &[(&str, String)]) -> Result<Vec<String>, String> {
+    split(template)?.iter().map(|word| expand(word, vars)).collect()
+}
+
+fn split(template: &str) -> Result<Vec<String>, String> {
+    shell_words::split(template).map_err(|e| format!("invalid command template: {}", e))
+}
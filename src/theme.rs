@@ -0,0 +1,271 @@
+use anyhow::{anyhow, bail, Result};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::{collections::HashMap, str::FromStr};
+
+/// User-defined theme from the `[themes.<name>]` section of `config.toml`.
+/// Unset colors are taken from `base` (default: `dark`).
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSpec {
+    pub base: Option<String>,
+    pub text: Option<String>,
+    pub border: Option<String>,
+    pub highlight: Option<String>,
+    pub selected: Option<String>,
+    pub input: Option<String>,
+    pub placeholder: Option<String>,
+    pub hint: Option<String>,
+    pub error: Option<String>,
+    pub notice: Option<String>,
+    pub danger: Option<String>,
+    pub accent: Option<String>,
+    pub profile: Option<String>,
+}
+
+/// Colors used throughout the UI.
+#[derive(Clone, Copy)]
+pub struct Theme {
+    text: Color,
+    border: Color,
+    highlight: Color,
+    selected: Color,
+    input: Color,
+    placeholder: Color,
+    hint: Color,
+    error: Color,
+    notice: Color,
+    danger: Color,
+    accent: Color,
+    profile: Color,
+    monochrome: bool, // NO_COLOR: 只使用粗体/反色等属性
+}
+
+impl Theme {
+    pub const BUILTIN: [&'static str; 3] = ["dark", "light", "high-contrast"];
+
+    fn dark() -> Self {
+        Self {
+            text: Color::Reset,
+            border: Color::Reset,
+            highlight: Color::Yellow,
+            selected: Color::Green,
+            input: Color::Yellow,
+            placeholder: Color::DarkGray,
+            hint: Color::Gray,
+            error: Color::Red,
+            notice: Color::Yellow,
+            danger: Color::Red,
+            accent: Color::Cyan,
+            profile: Color::Magenta,
+            monochrome: false,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            text: Color::Black,
+            border: Color::DarkGray,
+            highlight: Color::Blue,
+            selected: Color::Green,
+            input: Color::Blue,
+            placeholder: Color::Gray,
+            hint: Color::DarkGray,
+            error: Color::Red,
+            notice: Color::Magenta,
+            danger: Color::Red,
+            accent: Color::Blue,
+            profile: Color::Magenta,
+            monochrome: false,
+        }
+    }
+
+    fn high_contrast() -> Self {
+        Self {
+            text: Color::White,
+            border: Color::White,
+            highlight: Color::LightYellow,
+            selected: Color::LightGreen,
+            input: Color::LightYellow,
+            placeholder: Color::Gray,
+            hint: Color::White,
+            error: Color::LightRed,
+            notice: Color::LightYellow,
+            danger: Color::LightRed,
+            accent: Color::LightCyan,
+            profile: Color::LightMagenta,
+            monochrome: false,
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Resolve the configured theme, honoring the `NO_COLOR` environment variable.
+    pub fn load(name: Option<&str>, themes: &HashMap<String, ThemeSpec>) -> Result<Self> {
+        let mut theme = Self::resolve(name.unwrap_or("dark"), themes)?;
+        theme.monochrome = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Ok(theme)
+    }
+
+    fn resolve(name: &str, themes: &HashMap<String, ThemeSpec>) -> Result<Self> {
+        let Some(spec) = themes.get(name) else {
+            return Self::builtin(name).ok_or_else(|| {
+                anyhow!(
+                    "unknown theme '{}' (built-in themes: {})",
+                    name,
+                    Self::BUILTIN.join(", ")
+                )
+            });
+        };
+        let base = spec.base.as_deref().unwrap_or("dark");
+        if base == name {
+            bail!("theme '{}' cannot use itself as base", name);
+        }
+        let mut theme = match Self::builtin(base) {
+            Some(theme) => theme,
+            None if themes.contains_key(base) => bail!(
+                "theme '{}': base must be a built-in theme ({})",
+                name,
+                Self::BUILTIN.join(", ")
+            ),
+            None => bail!("theme '{}': unknown base theme '{}'", name, base),
+        };
+
+        let colors = [
+            ("text", &spec.text, &mut theme.text),
+            ("border", &spec.border, &mut theme.border),
+            ("highlight", &spec.highlight, &mut theme.highlight),
+            ("selected", &spec.selected, &mut theme.selected),
+            ("input", &spec.input, &mut theme.input),
+            ("placeholder", &spec.placeholder, &mut theme.placeholder),
+            ("hint", &spec.hint, &mut theme.hint),
+            ("error", &spec.error, &mut theme.error),
+            ("notice", &spec.notice, &mut theme.notice),
+            ("danger", &spec.danger, &mut theme.danger),
+            ("accent", &spec.accent, &mut theme.accent),
+            ("profile", &spec.profile, &mut theme.profile),
+        ];
+        for (key, value, color) in colors {
+            if let Some(value) = value {
+                *color = Color::from_str(value).map_err(|_| {
+                    anyhow!("theme '{}': invalid color '{}' for {}", name, value, key)
+                })?;
+            }
+        }
+        Ok(theme)
+    }
+
+    fn fg(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::default()
+        } else {
+            Style::default().fg(color)
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        self.fg(self.text)
+    }
+
+    pub fn border(&self) -> Style {
+        self.fg(self.border)
+    }
+
+    /// Highlighted row in lists.
+    pub fn highlight(&self) -> Style {
+        let style = self.fg(self.highlight).add_modifier(Modifier::BOLD);
+        if self.monochrome {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    }
+
+    /// Rows ticked in a multi-select list.
+    pub fn selected(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::UNDERLINED)
+        } else {
+            self.fg(self.selected)
+        }
+    }
+
+    /// Focused input field.
+    pub fn input(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            self.fg(self.input)
+        }
+    }
+
+    pub fn placeholder(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::DIM)
+        } else {
+            self.fg(self.placeholder)
+        }
+    }
+
+    /// Hints and the help bar.
+    pub fn hint(&self) -> Style {
+        self.fg(self.hint)
+    }
+
+    pub fn error(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            self.fg(self.error)
+        }
+    }
+
+    /// Informational dialogs.
+    pub fn notice(&self) -> Style {
+        self.fg(self.notice)
+    }
+
+    /// Destructive confirmations.
+    pub fn danger(&self) -> Style {
+        self.fg(self.danger)
+    }
+
+    /// Broadcast and help dialogs.
+    pub fn accent(&self) -> Style {
+        self.fg(self.accent)
+    }
+
+    /// Profile dialogs.
+    pub fn profile(&self) -> Style {
+        self.fg(self.profile)
+    }
+
+    /// Server name in a merged log stream; servers cycle through a fixed palette.
+    pub fn host(&self, index: usize) -> Style {
+        const COLORS: [Color; 6] = [
+            Color::Cyan,
+            Color::Magenta,
+            Color::Green,
+            Color::Yellow,
+            Color::Blue,
+            Color::LightRed,
+        ];
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(COLORS[index % COLORS.len()])
+        }
+    }
+
+    /// The active profile in the profile list.
+    pub fn current(&self) -> Style {
+        self.fg(self.accent).add_modifier(Modifier::BOLD)
+    }
+}
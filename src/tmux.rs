@@ -0,0 +1,70 @@
+/// True when sshx itself runs inside a tmux session.
+pub fn inside_tmux() -> bool {
+    std::env::var_os("TMUX").is_some_and(|v| !v.is_empty())
+}
+
+/// Value of a tmux option, e.g. `show_option(tmux, "-sv", "set-clipboard")`; None if tmux
+/// could not be asked or does not know the option.
+pub fn show_option(tmux: &str, flags: &str, option: &str) -> Option<String> {
+    let output = std::process::Command::new(tmux)
+        .args(["show-options", flags, option])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Major and minor version of tmux, e.g. `(3, 3)` for "tmux 3.3a"; None if tmux could not
+/// be asked or printed no version number (development builds print "tmux master").
+pub fn version(tmux: &str) -> Option<(u32, u32)> {
+    let output = std::process::Command::new(tmux)
+        .arg("-V")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    // 形如 "tmux 3.3a"、"tmux next-3.4" 或 "tmux 3.2-rc"
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let (major, rest) = text[start..].split_once('.')?;
+    let minor: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// tmux commands that open one pane per shell command, tiled and with synchronized input.
+///
+/// Inside tmux this is a single command that adds a window named `name` to the current
+/// session. Outside tmux it creates a detached session named `name`; the second command
+/// attaches to it.
+pub fn pane_commands(tmux: &str, name: &str, inside: bool, panes: &[String]) -> Vec<(String, Vec<String>)> {
+    let Some((first, rest)) = panes.split_first() else {
+        return Vec::new();
+    };
+    // 窗口名在当前会话中查找；会话外则使用新会话的当前窗口
+    let target = if inside { format!(":{}", name) } else { format!("{}:", name) };
+
+    let mut args: Vec<String> = if inside {
+        vec!["new-window".into(), "-n".into(), name.into(), first.clone()]
+    } else {
+        vec!["new-session".into(), "-d".into(), "-s".into(), name.into(), "-n".into(), name.into(), first.clone()]
+    };
+    for pane in rest {
+        // 每次分屏后重新平铺，避免窗格太小无法继续分割
+        for command in [
+            vec!["split-window".into(), "-t".into(), target.clone(), pane.clone()],
+            vec!["select-layout".into(), "-t".into(), target.clone(), "tiled".into()],
+        ] {
+            args.push(";".into());
+            args.extend(command);
+        }
+    }
+    args.extend([";", "set-window-option", "-t", &target, "synchronize-panes", "on"].map(String::from));
+
+    let mut commands = vec![(tmux.to_string(), args)];
+    if !inside {
+        commands.push((tmux.to_string(), vec!["attach-session".into(), "-t".into(), name.into()]));
+    }
+    commands
+}
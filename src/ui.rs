@@ -1,179 +1,614 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Clear, ListState, Wrap},
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, List, ListItem, Paragraph, Clear, ListState, Wrap, Gauge,
+    },
     Frame,
 };
 
-use crate::app::{App, InputMode, BroadcastPhase};
-use crate::server::Server;
+use crate::app::{App, InputMode, BroadcastPhase, ClickAreas, ConnectionForm, FormField, FormKind, ResultsView, TailPhase, TailView, TargetSelection, WatchView};
+use crate::broadcast::{diff_lines, DiffLine, HostRun, HostStatus, Rollout, RolloutState, WatchHost};
+use crate::command::format_command;
+use crate::config::{BroadcastOptions, FormDefaults};
+use crate::history::Snippet;
+use crate::report::Report;
+use crate::session::{Session, SessionStatus};
+use crate::tail::TailHostState;
+use crate::keymap::{Action, Keymap};
+use crate::server::{ProfileDefaults, Server};
+use crate::share;
+use crate::theme::Theme;
+use std::time::Instant;
 
 pub fn ui(f: &mut Frame, app: &mut App) {
-    let size = f.size();
+    let mut size = f.size();
+
+    // 有标签页时第一行是标签栏，其余部分显示当前标签页或服务器列表
+    let mut tab_bar = Vec::new();
+    if !app.sessions.is_empty() {
+        let prefix = app.keymap.keys_for(Action::Tabs).into_iter().next().unwrap_or_default();
+        let hint = if app.tab_prefix {
+            format!("0: servers | 1-9: tab | n/p: next/previous | x: close | {}: send {}", prefix, prefix)
+        } else {
+            format!("{}: tabs", prefix)
+        };
+        tab_bar = render_tab_bar(f, &app.theme, &app.sessions, app.active_tab, &hint, Rect { height: 1, ..size });
+        size = session_area(size);
+    }
+    if let Some(i) = app.active_tab {
+        render_session(f, &app.sessions[i], size);
+        app.click_areas = ClickAreas {
+            tab_bar,
+            ..ClickAreas::default()
+        };
+        return;
+    }
 
     // Determine help text based on current mode
     let help_text = match &app.input_mode {
-        InputMode::Normal => "Enter: SSH | s: SFTP | m: Mosh | p: Broadcast | n: New | e: Profile | c: Copy ID | i: Edit | d: Delete | q: Quit",
-        InputMode::Adding(_) => "Enter: Save | Esc: Cancel | Tab: Next Field",
-        InputMode::Editing(_) => "Enter: Save | Esc: Cancel | Tab: Next Field",
-        InputMode::ConfirmDelete(_) => "y: Confirm Delete | n/Esc: Cancel",
-        InputMode::ShowMessage(_) => "Press Enter, Esc or Space to close",
+        InputMode::Normal if app.tab_prefix => {
+            "0-9: Show Tab | n/p: Next/Previous Tab | Any Other Key: Cancel".to_string()
+        }
+        InputMode::Normal => app.keymap.help_text(),
+        InputMode::Form(_) => "Enter: Save | Esc: Cancel | Tab: Next Field".to_string(),
+        InputMode::ConfirmDelete(_) => "y: Confirm Delete | n/Esc: Cancel".to_string(),
+        InputMode::ConfirmQuit => "y: Quit | n/Esc: Cancel".to_string(),
+        InputMode::ShowMessage(_) => "Press Enter, Esc or Space to close".to_string(),
         InputMode::BroadcastCommand(s) => match s.phase {
-            BroadcastPhase::EnterCommand => "Enter: Next | Esc: Cancel",
-            BroadcastPhase::SelectServers => "Space: Toggle | j/k: Move | Enter: Execute | Esc: Cancel",
+            BroadcastPhase::EnterCommand => {
+                "Enter: Next | Up/Down: History | Ctrl+R: Search | Ctrl+O: Snippets | Ctrl+S: Save Snippet | Esc: Cancel".to_string()
+            }
+            BroadcastPhase::SearchHistory { .. } => "Ctrl+R: Older Match | Enter: Use | Esc: Back".to_string(),
+            BroadcastPhase::Snippets { .. } => "Enter: Use | d: Delete | j/k: Move | Esc: Back".to_string(),
+            BroadcastPhase::SaveSnippet(_) => "Enter: Save | Esc: Back".to_string(),
+            BroadcastPhase::SelectServers => {
+                "Space: Toggle | j/k: Move | +/-: Batch Size | </>: Pause | f: On Failure | Enter: Execute | w: Watch | Esc: Cancel".to_string()
+            }
+            BroadcastPhase::Preview { .. } => "Enter: Confirm | j/k: Scroll | Esc: Back".to_string(),
         },
-        InputMode::SelectingProfile => "Enter: Load | n: New Profile | Esc: Cancel",
-        InputMode::CreatingProfile(_) => "Enter: Create | Esc: Cancel",
+        InputMode::BroadcastResults(view) => match (view.output_scroll, view.rollout.state) {
+            (Some(_), _) => "j/k: Scroll | Esc: Back".to_string(),
+            (None, RolloutState::AwaitingDecision) => {
+                "c: Continue | s: Stop | Enter: Output | g: Group | j/k: Move".to_string()
+            }
+            (None, _) if view.rollout.is_active() => {
+                "s/Esc: Stop After This Batch | Enter: Output | g: Group | j/k: Move".to_string()
+            }
+            (None, _) if view.grouped => "Enter: Output | d: Mark/Diff | g: By Host | e/m: Export JSON/Markdown | Esc: Close".to_string(),
+            (None, _) => "Enter: Output | g: Group By Output | e/m: Export JSON/Markdown | j/k: Move | Esc: Close".to_string(),
+        },
+        InputMode::RunHistory { .. } => "Enter: Show Results | j/k: Move | Esc: Back".to_string(),
+        InputMode::TailSetup(setup) => match setup.phase {
+            TailPhase::Source => "Enter: Next | Tab: File/journald Unit | Esc: Cancel".to_string(),
+            TailPhase::SelectServers => "Space: Toggle | j/k: Move | Enter: Start | Esc: Back".to_string(),
+        },
+        InputMode::QuickConnect { .. } => "Enter: Connect | Ctrl+U: Clear | Esc: Cancel".to_string(),
+        InputMode::ConfirmSave(_) => "y/Enter: Save | n/Esc: Don't Save".to_string(),
+        InputMode::Share { .. } => "c: Copy | Tab: Next Format | Drag: Select Text | Esc: Close".to_string(),
+        InputMode::Import { .. } => "Enter: Import | Ctrl+U: Clear | Esc: Cancel".to_string(),
+        InputMode::TmuxSelect(_) => "Space: Toggle | j/k: Move | Enter: Open Panes | Esc: Cancel".to_string(),
+        InputMode::Tail(view) => match view.filter_input {
+            Some(_) => "Enter: Apply (empty shows all) | Esc: Cancel".to_string(),
+            None => "Space: Pause | /: Filter | s: Save | k/j: Scroll | G: Follow | Esc: Stop".to_string(),
+        },
+        InputMode::Watch(view) => match view.output_scroll {
+            Some(_) => "j/k: Scroll | Esc: Back".to_string(),
+            None => "Enter: Output | Space: Pause | r: Run Now | +/-: Interval | j/k: Move | Esc: Stop".to_string(),
+        },
+        InputMode::SelectingProfile => "Enter: Load | n: New Profile | Esc: Cancel".to_string(),
+        InputMode::CreatingProfile(_) => "Enter: Create | Esc: Cancel".to_string(),
+        InputMode::Help => "Esc/q/?: Close".to_string(),
     };
 
     // Calculate needed height for help text based on width
     // Inner width is size.width - 2 (borders)
     let inner_width = size.width.saturating_sub(2);
+    // Rough estimate of lines needed: total length / inner width, rounded up
     let help_lines = if inner_width > 0 {
-        // Rough estimate of lines needed: total length / inner width + 1
-        (help_text.len() as u16 + inner_width - 1) / inner_width
+        (help_text.len() as u16).div_ceil(inner_width)
     } else {
         1
     };
-    let help_height = help_lines + 2; // +2 for borders
+    // 内联模式下帮助只占一行且不带边框，完整列表用 ? 查看
+    let help_height = if app.config.inline { 1 } else { help_lines + 2 }; // +2 for borders
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(help_height)].as_ref())
         .split(size);
 
+    let defaults = app.connection_defaults();
     let items: Vec<ListItem> = app
         .servers
         .iter()
         .map(|s| {
-            let content = format!("{} ({}) - {}:{}", s.name, s.user, s.host, s.port);
-            ListItem::new(content).style(Style::default())
+            let content = format!("{} {}", s.name, s.describe(&defaults));
+            ListItem::new(content).style(app.theme.text())
         })
         .collect();
 
-    let title = format!(" SSHX - Servers [{}] ", app.current_profile);
+    let title = if app.pick.is_some() {
+        format!(" SSHX - Pick a Server [{}] ", app.current_profile)
+    } else if app.config.dry_run {
+        format!(" SSHX - Servers [{}] (dry run) ", app.current_profile)
+    } else {
+        format!(" SSHX - Servers [{}] ", app.current_profile)
+    };
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(title))
-        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+        .block(Block::default().borders(Borders::ALL).border_style(app.theme.border()).title(title))
+        .highlight_style(app.theme.highlight())
         .highlight_symbol("> ");
 
     f.render_stateful_widget(list, chunks[0], &mut app.state);
+    app.click_areas = ClickAreas {
+        server_list: chunks[0].inner(&Margin::new(1, 1)),
+        tab_bar,
+        ..ClickAreas::default()
+    };
 
     // Help text with wrapping
-    let help = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::Gray))
-        .block(Block::default().borders(Borders::ALL).title(" Help "))
-        .wrap(Wrap { trim: true });
-    f.render_widget(help, chunks[1]);
-
-    // Popup for Adding Server
-    if let InputMode::Adding(state) = &app.input_mode {
-        render_connection_form(
-            f,
-            " Add New Connection ",
-            state.field_idx,
-            &[&state.name, &state.user, &state.host, &state.port, &state.jump_host],
-        );
+    let help = Paragraph::new(help_text.as_str()).style(app.theme.hint());
+    if app.config.inline {
+        f.render_widget(help, chunks[1]);
+    } else {
+        let help = help
+            .block(Block::default().borders(Borders::ALL).border_style(app.theme.border()).title(" Help "))
+            .wrap(Wrap { trim: true });
+        f.render_widget(help, chunks[1]);
     }
 
-    // Popup for Editing Server
-    if let InputMode::Editing(state) = &app.input_mode {
-        render_connection_form(
-            f,
-            " Edit Connection ",
-            state.field_idx,
-            &[&state.name, &state.user, &state.host, &state.port, &state.jump_host],
-        );
+    // Popup for Adding / Editing Server
+    if let InputMode::Form(form) = &app.input_mode {
+        app.click_areas.form_fields =
+            render_connection_form(f, &app.theme, form, &app.defaults, &app.config.form);
     }
 
     // Popup for Delete Confirmation
     if let InputMode::ConfirmDelete(idx) = &app.input_mode {
         if *idx < app.servers.len() {
             let server_name = &app.servers[*idx].name;
-            render_confirm_dialog(f, server_name);
+            render_confirm_dialog(f, &app.theme, " Confirm Delete ", &format!("Delete \"{}\"?", server_name));
+        }
+    }
+
+    // Popup for Quit Confirmation
+    if let InputMode::ConfirmQuit = &app.input_mode {
+        let message = match app.sessions.len() {
+            0 => "Quit sshx?".to_string(),
+            1 => "Quit sshx and close the open tab?".to_string(),
+            n => format!("Quit sshx and close {} open tabs?", n),
+        };
+        render_confirm_dialog(f, &app.theme, " Confirm Quit ", &message);
+    }
+
+    // Ad-hoc connection
+    if let InputMode::QuickConnect { input, error } = &app.input_mode {
+        render_broadcast_input(
+            f,
+            &app.theme,
+            " Quick Connect ",
+            "[user@]host[:port] or ssh://…, optionally with -J jump",
+            input,
+            error.as_deref().unwrap_or("Connects right away; you can save the server afterwards"),
+        );
+    }
+    if let InputMode::ConfirmSave(server) = &app.input_mode {
+        let message = format!(
+            "Save {}:{} to {}?",
+            server.address(&defaults),
+            server.effective_port(&defaults),
+            app.current_profile
+        );
+        render_confirm_dialog(f, &app.theme, " Save Server ", &message);
+    }
+
+    // Share and import snippets
+    if let InputMode::Share { index, format } = &app.input_mode {
+        if let Some(server) = app.servers.get(*index) {
+            let title = format!(" Share {} as {} ", server.name, format.label());
+            match share::export(server, &defaults, *format) {
+                Ok(text) => render_text_popup(f, &app.theme, &title, &text, "c: copy | Tab: next format | Esc: close", false),
+                Err(e) => render_text_popup(f, &app.theme, &title, "", &e, false),
+            }
         }
     }
+    if let InputMode::Import { text, error } = &app.input_mode {
+        let hint = error.as_deref().unwrap_or("Paste ssh:// URIs, a JSON or a TOML snippet | Enter: add to this profile | Esc: cancel");
+        render_text_popup(f, &app.theme, " Import Servers ", text, hint, true);
+    }
 
     // Popup for Message
     if let InputMode::ShowMessage(msg) = &app.input_mode {
-        render_message_dialog(f, msg);
+        render_message_dialog(f, &app.theme, msg);
     }
 
     // Popup for Broadcast Command
     if let InputMode::BroadcastCommand(state) = &app.input_mode {
-        match state.phase {
+        match &state.phase {
             BroadcastPhase::EnterCommand => {
-                render_broadcast_command_input(f, &state.command);
+                let title = match &state.snippet_name {
+                    Some(name) => format!("Command (snippet: {})", name),
+                    None => "Command".to_string(),
+                };
+                render_broadcast_input(
+                    f,
+                    &app.theme,
+                    " Broadcast Command - Enter Command ",
+                    &title,
+                    &state.command,
+                    "Enter: confirm | Up/Down: history | Ctrl+R: search | Ctrl+O/S: snippets | Esc: cancel",
+                );
+            }
+            BroadcastPhase::SearchHistory { query, found } => {
+                // 搜索时输入框显示匹配到的历史命令
+                let matched = found.map(|i| app.history.entries()[i].as_str()).unwrap_or("");
+                let title = if found.is_some() || query.is_empty() {
+                    format!("reverse-i-search: {}", query)
+                } else {
+                    format!("failing reverse-i-search: {}", query)
+                };
+                render_broadcast_input(
+                    f,
+                    &app.theme,
+                    " Broadcast Command - History ",
+                    &title,
+                    matched,
+                    "Type to search | Ctrl+R: older | Enter: use | Esc: back",
+                );
+            }
+            BroadcastPhase::Snippets { cursor } => {
+                let (area, offset) = render_snippet_list(f, &app.theme, app.snippets.items(), *cursor);
+                app.click_areas.popup_list = area;
+                app.click_areas.popup_offset = offset;
+            }
+            BroadcastPhase::SaveSnippet(name) => {
+                render_broadcast_input(
+                    f,
+                    &app.theme,
+                    " Broadcast Command - Save Snippet ",
+                    "Snippet Name",
+                    name,
+                    "Enter: save (replaces a snippet with the same name) | Esc: back",
+                );
             }
             BroadcastPhase::SelectServers => {
-                render_broadcast_server_select(f, &app.servers, &state.selected, state.cursor);
+                let (area, offset) = render_broadcast_server_select(
+                    f,
+                    &app.theme,
+                    &app.servers,
+                    &defaults,
+                    &state.targets,
+                    SelectFor::Broadcast(&state.options),
+                );
+                app.click_areas.popup_list = area;
+                app.click_areas.popup_offset = offset;
+            }
+            BroadcastPhase::Preview { targets, cursor } => {
+                render_broadcast_preview(
+                    f,
+                    &app.theme,
+                    &app.servers,
+                    &app.config.programs.ssh,
+                    targets,
+                    *cursor,
+                    app.config.dry_run,
+                );
+            }
+        }
+    }
+
+    // Progress and results of a running broadcast
+    if let InputMode::BroadcastResults(view) = &app.input_mode {
+        let (area, offset) = render_broadcast_results(f, &app.theme, view);
+        app.click_areas.popup_list = area;
+        app.click_areas.popup_offset = offset;
+    }
+
+    // Live table of a watched command
+    if let InputMode::Watch(view) = &app.input_mode {
+        let (area, offset) = render_watch(f, &app.theme, view);
+        app.click_areas.popup_list = area;
+        app.click_areas.popup_offset = offset;
+    }
+
+    // Log tailing
+    if let InputMode::TailSetup(setup) = &app.input_mode {
+        match setup.phase {
+            TailPhase::Source => {
+                let label = format!("{} (Tab to switch)", setup.source.label());
+                render_broadcast_input(
+                    f,
+                    &app.theme,
+                    " Tail Logs ",
+                    &label,
+                    &setup.target,
+                    "Path like /var/log/syslog or a unit like nginx; {name} etc. are filled in",
+                );
+            }
+            TailPhase::SelectServers => {
+                let (area, offset) = render_broadcast_server_select(
+                    f,
+                    &app.theme,
+                    &app.servers,
+                    &defaults,
+                    &setup.targets,
+                    SelectFor::Tail,
+                );
+                app.click_areas.popup_list = area;
+                app.click_areas.popup_offset = offset;
             }
         }
     }
+    if let InputMode::Tail(view) = &app.input_mode {
+        render_tail(f, &app.theme, view);
+    }
+
+    // Servers to open in tmux panes
+    if let InputMode::TmuxSelect(targets) = &app.input_mode {
+        let (area, offset) =
+            render_broadcast_server_select(f, &app.theme, &app.servers, &defaults, targets, SelectFor::Tmux);
+        app.click_areas.popup_list = area;
+        app.click_areas.popup_offset = offset;
+    }
+
+    // Past broadcast runs
+    if let InputMode::RunHistory { runs, cursor } = &app.input_mode {
+        let (area, offset) = render_run_history(f, &app.theme, runs, *cursor);
+        app.click_areas.popup_list = area;
+        app.click_areas.popup_offset = offset;
+    }
 
     // Popup for Profile Selection
     if let InputMode::SelectingProfile = &app.input_mode {
-        render_profile_selection(f, &app.profiles, &mut app.profile_state, &app.current_profile);
+        app.click_areas.popup_list =
+            render_profile_selection(f, &app.theme, &app.profiles, &mut app.profile_state, &app.current_profile);
+        app.click_areas.popup_offset = app.profile_state.offset();
     }
 
     // Popup for Profile Creation
     if let InputMode::CreatingProfile(name) = &app.input_mode {
-        render_profile_creation(f, name);
+        render_profile_creation(f, &app.theme, name);
+    }
+
+    // Popup for Key Bindings
+    if let InputMode::Help = &app.input_mode {
+        render_help_overlay(f, &app.theme, &app.keymap);
+    }
+
+    if let Some((message, _)) = &app.toast {
+        render_toast(f, &app.theme, message, chunks[0].inner(&Margin::new(1, 1)));
     }
 }
 
-fn render_connection_form(f: &mut Frame, title: &str, field_idx: usize, values: &[&String]) {
+/// A one-line notice in the bottom-right corner of `area` that does not take the focus.
+fn render_toast(f: &mut Frame, theme: &Theme, message: &str, area: Rect) {
+    let width = (message.chars().count() as u16 + 4).min(area.width);
+    let height = 3.min(area.height);
+    let toast = Rect {
+        x: area.right() - width,
+        y: area.bottom() - height,
+        width,
+        height,
+    };
+    let block = Block::default().borders(Borders::ALL).style(theme.notice());
+    f.render_widget(Clear, toast);
+    f.render_widget(Paragraph::new(format!(" {}", message)).style(theme.text()).block(block), toast);
+}
+
+/// Where the current tab is drawn: everything below the tab bar.
+pub fn session_area(size: Rect) -> Rect {
+    Rect {
+        y: size.y + 1,
+        height: size.height.saturating_sub(1),
+        ..size
+    }
+}
+
+/// One line with the server list and every tab; returns the area of each, list first.
+fn render_tab_bar(
+    f: &mut Frame,
+    theme: &Theme,
+    sessions: &[Session],
+    active: Option<usize>,
+    hint: &str,
+    area: Rect,
+) -> Vec<Rect> {
+    let mut titles = vec![(" 0 servers ".to_string(), active.is_none())];
+    for (i, session) in sessions.iter().enumerate() {
+        let title = match session.status {
+            SessionStatus::Running => format!(" {} {} ", i + 1, session.name),
+            SessionStatus::Exited(code) => format!(" {} {} (exit {}) ", i + 1, session.name, code),
+        };
+        titles.push((title, active == Some(i)));
+    }
+
+    let mut spans = Vec::new();
+    let mut areas = Vec::new();
+    let mut x = area.x;
+    for (title, current) in titles {
+        let width = (title.chars().count() as u16).min((area.x + area.width).saturating_sub(x));
+        areas.push(Rect { x, width, ..area });
+        x += width;
+        spans.push(Span::styled(title, if current { theme.highlight() } else { theme.text() }));
+        spans.push(Span::styled("│", theme.border()));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+
+    // 提示靠右显示，放不下时省略
+    let used = x.saturating_sub(area.x) + areas.len() as u16;
+    let width = hint.chars().count() as u16 + 1;
+    if used + width <= area.width {
+        let hint_area = Rect {
+            x: area.x + area.width - width,
+            width,
+            ..area
+        };
+        f.render_widget(Paragraph::new(hint).style(theme.hint()), hint_area);
+    }
+    areas
+}
+
+/// Copy the terminal screen of a tab into the frame.
+fn render_session(f: &mut Frame, session: &Session, area: Rect) {
+    let parser = session.screen();
+    let screen = parser.screen();
+    let buf = f.buffer_mut();
+    for row in 0..area.height {
+        for col in 0..area.width {
+            let Some(cell) = screen.cell(row, col) else { continue };
+            // 宽字符的后半格由前一格负责绘制
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let mut style = Style::default().fg(vt_color(cell.fgcolor())).bg(vt_color(cell.bgcolor()));
+            if cell.bold() {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if cell.italic() {
+                style = style.add_modifier(Modifier::ITALIC);
+            }
+            if cell.underline() {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            if cell.inverse() {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let contents = cell.contents();
+            let target = buf.get_mut(area.x + col, area.y + row);
+            target.set_symbol(if contents.is_empty() { " " } else { &contents });
+            target.set_style(style);
+        }
+    }
+
+    if session.scroll > 0 {
+        let label = format!(" [{} lines back] ", session.scroll);
+        let width = (label.len() as u16).min(area.width);
+        let label_area = Rect {
+            x: area.x + area.width - width,
+            width,
+            height: 1,
+            ..area
+        };
+        f.render_widget(Paragraph::new(label).style(Style::default().add_modifier(Modifier::REVERSED)), label_area);
+    } else if let SessionStatus::Exited(code) = session.status {
+        let label = format!(" Session ended (exit {}); press any key to close the tab ", code);
+        let bottom = Rect {
+            y: area.y + area.height.saturating_sub(1),
+            height: area.height.min(1),
+            ..area
+        };
+        f.render_widget(Paragraph::new(label).style(Style::default().add_modifier(Modifier::REVERSED)), bottom);
+    } else if !screen.hide_cursor() {
+        let (row, col) = screen.cursor_position();
+        if row < area.height && col < area.width {
+            f.set_cursor(area.x + col, area.y + row);
+        }
+    }
+}
+
+fn vt_color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+/// Returns the area of each input field.
+fn render_connection_form(
+    f: &mut Frame,
+    theme: &Theme,
+    form: &ConnectionForm,
+    defaults: &ProfileDefaults,
+    fallback: &FormDefaults,
+) -> Vec<Rect> {
     let size = f.size();
-    // Use fixed height of 22 lines (enough for 5 fields * 3 lines + margins/borders)
-    let area = centered_fixed_rect(60, 22, size);
+    let fields = form.fields();
+    // 3 lines per field plus margins/borders
+    let wanted = fields.len() as u16 * 3 + 7;
+    let area = centered_fixed_rect(60, wanted.min(size.height), size);
+    // 放不下时（如内联模式）只显示当前字段附近的几个，随光标滚动
+    let margin = if area.height >= wanted { 2 } else { 1 };
+    let visible = (area.height.saturating_sub(margin * 2) / 3).max(1) as usize;
+    let first = form.field_idx.saturating_sub(visible - 1);
+    let shown = first..fields.len().min(first + visible);
 
-    let block = Block::default().borders(Borders::ALL).title(title);
+    let title = match form.kind {
+        FormKind::Add => " Add New Connection ",
+        FormKind::Edit(_) => " Edit Connection ",
+        FormKind::Defaults => " Profile Defaults ",
+    };
+    let title = if shown.len() < fields.len() {
+        format!("{}- field {}/{} ", title, form.field_idx + 1, fields.len())
+    } else {
+        title.to_string()
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.border())
+        .title(title)
+        .style(theme.text());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
-    let input_layout = Layout::default()
+    let mut constraints: Vec<Constraint> = shown.clone().map(|_| Constraint::Length(3)).collect();
+    constraints.push(Constraint::Min(0));
+    let rows = Layout::default()
         .direction(Direction::Vertical)
-        .margin(2)
-        .constraints(
-            [
-                Constraint::Length(3), // Name
-                Constraint::Length(3), // User
-                Constraint::Length(3), // Host
-                Constraint::Length(3), // Port
-                Constraint::Length(3), // Jump Host
-                Constraint::Min(1),
-            ]
-            .as_ref(),
-        )
+        .margin(margin)
+        .constraints(constraints)
         .split(area);
+    // 未显示的字段占位为空区域，点击不到
+    let mut input_layout = vec![Rect::default(); fields.len()];
+    input_layout[shown.clone()].copy_from_slice(&rows[..shown.len()]);
 
-    let fields_labels = [
-        "Name",
-        "User (default: root)",
-        "Host/IP",
-        "Port (default: 22)",
-        "Jump Host (optional, e.g. user@host:port)",
-    ];
-
-    for (i, label) in fields_labels.iter().enumerate() {
-        let value = values[i];
-        let mut style = Style::default();
-        if field_idx == i {
-            style = style.fg(Color::Yellow);
+    for (i, field) in fields.iter().enumerate().filter(|(i, _)| shown.contains(i)) {
+        let driver_value;
+        let value = if *field == FormField::Driver {
+            driver_value = format!("< {} >", form.driver.name());
+            &driver_value
+        } else {
+            form.value(*field)
+        };
+        let style = if form.field_idx == i {
+            theme.input()
+        } else {
+            theme.text()
+        };
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme.border())
+            .title(field.label(form.driver));
+        // Inline validation error in the bottom border
+        if let Some(error) = form.errors.get(field) {
+            block = block
+                .border_style(theme.error())
+                .title(
+                    Title::from(Span::styled(format!(" {} ", error), theme.error()))
+                        .position(Position::Bottom),
+                );
         }
-        let input = Paragraph::new(value.as_str())
-            .style(style)
-            .block(Block::default().borders(Borders::ALL).title(*label));
+        // Empty fields show the value they inherit
+        let input = match form.placeholder(*field, defaults, fallback) {
+            Some(placeholder) if value.is_empty() => Paragraph::new(placeholder)
+                .style(theme.placeholder())
+                .block(block),
+            _ => Paragraph::new(value).style(style).block(block),
+        };
         f.render_widget(input, input_layout[i]);
 
         // Show cursor in the active input field
-        if field_idx == i {
+        if form.field_idx == i && *field != FormField::Driver {
             f.set_cursor(
                 input_layout[i].x + value.len() as u16 + 1,
                 input_layout[i].y + 1,
             );
         }
     }
+    input_layout
 }
 
 fn centered_fixed_rect(width_percent: u16, height: u16, r: Rect) -> Rect {
@@ -202,14 +637,14 @@ fn centered_fixed_rect(width_percent: u16, height: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn render_confirm_dialog(f: &mut Frame, server_name: &str) {
+fn render_confirm_dialog(f: &mut Frame, theme: &Theme, title: &str, message: &str) {
     let size = f.size();
     let area = centered_fixed_rect(50, 7, size);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .title(" Confirm Delete ")
-        .style(Style::default().fg(Color::Red));
+        .title(title)
+        .style(theme.danger());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
@@ -219,25 +654,26 @@ fn render_confirm_dialog(f: &mut Frame, server_name: &str) {
         .constraints([Constraint::Length(2), Constraint::Length(2)].as_ref())
         .split(area);
 
-    let msg = format!("Delete \"{}\"?", server_name);
-    let text = Paragraph::new(msg).style(Style::default().fg(Color::White));
+    let text = Paragraph::new(message).style(theme.text());
     f.render_widget(text, inner[0]);
 
     let hint = Paragraph::new("Press 'y' to confirm, 'n' or Esc to cancel")
-        .style(Style::default().fg(Color::Gray));
+        .style(theme.hint());
     f.render_widget(hint, inner[1]);
 }
 
-fn render_message_dialog(f: &mut Frame, message: &str) {
+fn render_message_dialog(f: &mut Frame, theme: &Theme, message: &str) {
     let size = f.size();
-    let lines: Vec<&str> = message.lines().collect();
-    let height = (lines.len() as u16 + 4).min(20);
+    // 长行会自动换行，高度按换行后的行数计算
+    let width = (size.width * 60 / 100).saturating_sub(2).max(1) as usize;
+    let lines: usize = message.lines().map(|line| line.chars().count().max(1).div_ceil(width)).sum();
+    let height = (lines as u16 + 4).min(20).min(size.height);
     let area = centered_fixed_rect(60, height, size);
 
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" Notice ")
-        .style(Style::default().fg(Color::Yellow));
+        .style(theme.notice());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
@@ -247,22 +683,29 @@ fn render_message_dialog(f: &mut Frame, message: &str) {
         .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
         .split(area);
 
-    let text = Paragraph::new(message).style(Style::default().fg(Color::White));
+    let text = Paragraph::new(message).style(theme.text()).wrap(Wrap { trim: false });
     f.render_widget(text, inner[0]);
 
     let hint = Paragraph::new("Press Enter, Esc or Space to close")
-        .style(Style::default().fg(Color::Gray));
+        .style(theme.hint());
     f.render_widget(hint, inner[1]);
 }
 
-fn render_broadcast_command_input(f: &mut Frame, command: &str) {
+fn render_broadcast_input(
+    f: &mut Frame,
+    theme: &Theme,
+    title: &str,
+    label: &str,
+    value: &str,
+    hint: &str,
+) {
     let size = f.size();
     let area = centered_fixed_rect(60, 7, size);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .title(" Broadcast Command - Enter Command ")
-        .style(Style::default().fg(Color::Cyan));
+        .title(title)
+        .style(theme.accent());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
@@ -272,35 +715,95 @@ fn render_broadcast_command_input(f: &mut Frame, command: &str) {
         .constraints([Constraint::Length(3), Constraint::Length(1)].as_ref())
         .split(area);
 
-    let input = Paragraph::new(command)
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Command"));
+    let input = Paragraph::new(value)
+        .style(theme.input())
+        .block(Block::default().borders(Borders::ALL).title(label));
     f.render_widget(input, inner[0]);
 
     // Show cursor at end of input
-    f.set_cursor(inner[0].x + command.len() as u16 + 1, inner[0].y + 1);
+    f.set_cursor(inner[0].x + value.len() as u16 + 1, inner[0].y + 1);
 
-    let hint = Paragraph::new("Enter: confirm | Esc: cancel")
-        .style(Style::default().fg(Color::Gray));
+    let hint = Paragraph::new(hint)
+        .style(theme.hint());
     f.render_widget(hint, inner[1]);
 }
 
-fn render_broadcast_server_select(f: &mut Frame, servers: &[Server], selected: &[bool], cursor: usize) {
+/// A full-width popup with a block of text, e.g. a snippet to copy or one being pasted.
+/// There are no side borders and lines are not wrapped, so selecting whole lines in the
+/// terminal copies the text unchanged.
+fn render_text_popup(f: &mut Frame, theme: &Theme, title: &str, text: &str, hint: &str, cursor: bool) {
     let size = f.size();
-    let height = (servers.len() as u16 + 5).min(size.height.saturating_sub(4));
+    let lines: Vec<&str> = text.lines().collect();
+    // 光标所在的行；以换行结尾时是其后的空行
+    let row = if text.ends_with('\n') { lines.len() } else { lines.len().saturating_sub(1) };
+    let height = (row as u16 + 4).min(size.height);
+    let area = centered_fixed_rect(100, height, size);
+
+    let block = Block::default()
+        .borders(Borders::TOP | Borders::BOTTOM)
+        .title(title)
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .vertical_margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    // 粘贴的内容超出高度时显示末尾
+    let scroll = if cursor { (row + 1).saturating_sub(inner[0].height as usize) } else { 0 };
+    let body = Paragraph::new(text).style(theme.input()).scroll((scroll as u16, 0));
+    f.render_widget(body, inner[0]);
+
+    if cursor {
+        let last = if text.ends_with('\n') { "" } else { lines.last().copied().unwrap_or("") };
+        let x = inner[0].x.saturating_add(last.chars().count() as u16);
+        let y = inner[0].y + (row - scroll) as u16;
+        f.set_cursor(x.min(inner[0].right().saturating_sub(1)), y);
+    }
+
+    let hint = Paragraph::new(hint).style(theme.hint());
+    f.render_widget(hint, inner[1]);
+}
+
+/// What the servers are being picked for; decides the title and the line below the list.
+enum SelectFor<'a> {
+    Broadcast(&'a BroadcastOptions),
+    Tail,
+    Tmux,
+}
+
+fn render_broadcast_server_select(
+    f: &mut Frame,
+    theme: &Theme,
+    servers: &[Server],
+    defaults: &ProfileDefaults,
+    targets: &TargetSelection,
+    purpose: SelectFor,
+) -> (Rect, usize) {
+    let size = f.size();
+    let height = (servers.len() as u16 + 6).min(size.height.saturating_sub(4));
     let area = centered_fixed_rect(60, height, size);
+    let (selected, cursor) = (&targets.selected, targets.cursor);
 
+    let title = match purpose {
+        SelectFor::Broadcast(_) => " Broadcast Command - Select Servers ",
+        SelectFor::Tail => " Tail Logs - Select Servers ",
+        SelectFor::Tmux => " tmux Panes - Select Servers ",
+    };
     let block = Block::default()
         .borders(Borders::ALL)
-        .title(" Broadcast Command - Select Servers ")
-        .style(Style::default().fg(Color::Cyan));
+        .title(title)
+        .style(theme.accent());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
     let inner = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
-        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .constraints([Constraint::Min(1), Constraint::Length(1), Constraint::Length(1)].as_ref())
         .split(area);
 
     let items: Vec<ListItem> = servers
@@ -308,28 +811,617 @@ fn render_broadcast_server_select(f: &mut Frame, servers: &[Server], selected: &
         .enumerate()
         .map(|(i, s)| {
             let check = if selected[i] { "[x]" } else { "[ ]" };
-            let content = format!("{} {} ({}) - {}:{}", check, s.name, s.user, s.host, s.port);
+            let content = format!("{} {} {}", check, s.name, s.describe(defaults));
             let style = if i == cursor {
-                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                theme.highlight()
+            } else if !s.driver.is_ssh() {
+                // 非 ssh driver 不能广播命令
+                theme.placeholder()
             } else if selected[i] {
-                Style::default().fg(Color::Green)
+                theme.selected()
             } else {
-                Style::default()
+                theme.text()
             };
             ListItem::new(content).style(style)
         })
         .collect();
 
+    // Stateful so long lists scroll to keep the cursor visible
+    let list = List::new(items);
+    let mut state = ListState::default().with_selected(Some(cursor));
+    f.render_stateful_widget(list, inner[0], &mut state);
+
+    let (options, hint) = match purpose {
+        SelectFor::Broadcast(options) => {
+            let batch = match options.batch_size {
+                0 => "all".to_string(),
+                n => n.to_string(),
+            };
+            let options = format!(
+                "Batch size: {} (+/-) | Pause: {}s (</>) | On failure: {} (f) | Watch: every {}s ([/])",
+                batch,
+                options.pause,
+                options.on_failure.name(),
+                options.interval
+            );
+            (options, "Space: toggle | j/k: move | Enter: execute | w: watch | Esc: cancel")
+        }
+        SelectFor::Tail => (
+            "New lines from every ticked server are merged into one stream".to_string(),
+            "Space: toggle | j/k: move | Enter: start tailing | Esc: back",
+        ),
+        SelectFor::Tmux => (
+            "One ssh pane per server; typing goes to every pane".to_string(),
+            "Space: toggle | j/k: move | Enter: open panes | Esc: cancel",
+        ),
+    };
+    f.render_widget(Paragraph::new(options).style(theme.text()), inner[1]);
+    f.render_widget(Paragraph::new(hint).style(theme.hint()), inner[2]);
+
+    (inner[0], state.offset())
+}
+
+fn render_broadcast_preview(
+    f: &mut Frame,
+    theme: &Theme,
+    servers: &[Server],
+    program: &str,
+    targets: &[(usize, Vec<String>)],
+    cursor: usize,
+    dry_run: bool,
+) {
+    let size = f.size();
+    // 每个目标两行：服务器名和完整命令
+    let height = (targets.len() as u16 * 2 + 4).min(size.height.saturating_sub(4));
+    let area = centered_fixed_rect(80, height, size);
+
+    let title = if dry_run {
+        " Broadcast Command - Preview (dry run) "
+    } else {
+        " Broadcast Command - Preview "
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    let items: Vec<ListItem> = targets
+        .iter()
+        .map(|(idx, args)| {
+            ListItem::new(vec![
+                Line::from(servers[*idx].name.clone()),
+                Line::styled(format!("  $ {}", format_command(program, args)), theme.hint()),
+            ])
+            .style(theme.text())
+        })
+        .collect();
     let list = List::new(items)
-        .block(Block::default());
-    f.render_widget(list, inner[0]);
+        .highlight_style(theme.highlight())
+        .highlight_symbol("> ");
+    let mut state = ListState::default().with_selected(Some(cursor));
+    f.render_stateful_widget(list, inner[0], &mut state);
+
+    let hint = if dry_run {
+        format!("Enter: print {} command(s) | Esc: back", targets.len())
+    } else {
+        format!("Enter: run on {} server(s) | Esc: back", targets.len())
+    };
+    f.render_widget(Paragraph::new(hint).style(theme.hint()), inner[1]);
+}
+
+/// Returns the host list area and its scroll offset.
+fn render_broadcast_results(f: &mut Frame, theme: &Theme, view: &ResultsView) -> (Rect, usize) {
+    let size = f.size();
+    let rollout = &view.rollout;
+    let area = centered_fixed_rect(80, size.height.saturating_sub(4), size);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Broadcast: {} ", rollout.command))
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    // Overall progress
+    let (done, failed) = rollout.counts();
+    let total = rollout.hosts.len();
+    let state = match rollout.state {
+        RolloutState::Running => "running".to_string(),
+        RolloutState::Pausing(until) => format!(
+            "next batch in {}s",
+            until.saturating_duration_since(Instant::now()).as_secs() + 1
+        ),
+        RolloutState::AwaitingDecision => "failures in this batch - continue?".to_string(),
+        RolloutState::Stopped if rollout.is_active() => "stopping after this batch".to_string(),
+        RolloutState::Stopped => "stopped".to_string(),
+        RolloutState::Finished => "finished".to_string(),
+    };
+    let label = format!(
+        "Batch {}/{} | {}/{} done | {} failed | {}",
+        rollout.batch,
+        rollout.batch_count(),
+        done,
+        total,
+        failed,
+        state
+    );
+    let gauge_style = if failed > 0 { theme.error() } else { theme.selected() };
+    let gauge = Gauge::default()
+        .gauge_style(gauge_style)
+        .ratio(done as f64 / total.max(1) as f64)
+        .label(label);
+    f.render_widget(gauge, inner[0]);
 
-    let hint = Paragraph::new("Space: toggle | j/k: move | Enter: execute | Esc: cancel")
-        .style(Style::default().fg(Color::Gray));
+    let items: Vec<ListItem> = if view.grouped {
+        rollout
+            .clusters()
+            .iter()
+            .map(|cluster| {
+                let host = &rollout.hosts[cluster[0]];
+                let (status, style) = status_label(theme, &host.status);
+                let marker = match view.diff_base {
+                    Some(base) if cluster.contains(&base) => "*",
+                    _ => " ",
+                };
+                let hosts = if cluster.len() == 1 { "host" } else { "hosts" };
+                ListItem::new(format!(
+                    "{}{:<7} {:>4} {:<5}  {}",
+                    marker,
+                    status,
+                    cluster.len(),
+                    hosts,
+                    first_line(host)
+                ))
+                .style(style)
+            })
+            .collect()
+    } else {
+        rollout
+            .hosts
+            .iter()
+            .map(|host| {
+                let (status, style) = status_label(theme, &host.status);
+                let duration = host
+                    .duration
+                    .map(|d| format!("{:.1}s", d.as_secs_f64()))
+                    .unwrap_or_default();
+                ListItem::new(format!(
+                    "{:<7} {:<20} {:>7}  {}",
+                    status, host.name, duration, first_line(host)
+                ))
+                .style(style)
+            })
+            .collect()
+    };
+    let list = List::new(items)
+        .highlight_style(theme.highlight())
+        .highlight_symbol("> ");
+    let mut list_state = ListState::default().with_selected(Some(view.cursor));
+    f.render_stateful_widget(list, inner[1], &mut list_state);
+
+    let hint = match rollout.state {
+        RolloutState::AwaitingDecision => "c: continue | s: stop | Enter: output | g: group",
+        _ if rollout.is_active() => "s/Esc: stop after this batch | Enter: output | g: group",
+        _ if view.grouped && view.diff_base.is_some() => "d: diff with marked group | Enter: output | g: hosts | e/m: export | Esc: close",
+        _ if view.grouped => "d: mark for diff | Enter: output | g: hosts | e/m: export | Esc: close",
+        _ => "Enter: output | g: group by output | e: export JSON | m: export Markdown | Esc: close",
+    };
+    match &view.notice {
+        Some(notice) => f.render_widget(Paragraph::new(notice.as_str()).style(theme.notice()), inner[2]),
+        None => f.render_widget(Paragraph::new(hint).style(theme.hint()), inner[2]),
+    }
+
+    if let (Some(scroll), Some((old, new))) = (view.output_scroll, view.diff) {
+        render_output_diff(f, theme, rollout, old, new, scroll);
+    } else if let (Some(scroll), Some(idx)) = (view.output_scroll, view.selected_host()) {
+        let title = if view.grouped {
+            let cluster = rollout.clusters().into_iter().find(|c| c.contains(&idx)).unwrap_or_default();
+            let names: Vec<&str> = cluster.iter().map(|i| rollout.hosts[*i].name.as_str()).collect();
+            format!("{} hosts: {}", cluster.len(), names.join(", "))
+        } else {
+            rollout.hosts[idx].name.clone()
+        };
+        render_host_output(f, theme, &rollout.hosts[idx], &title, scroll);
+    }
+
+    (inner[1], list_state.offset())
+}
+
+fn render_tail(f: &mut Frame, theme: &Theme, view: &TailView) {
+    let size = f.size();
+    let tail = &view.tail;
+    let area = centered_fixed_rect(size.width.saturating_sub(4), size.height.saturating_sub(4), size);
+
+    let mut title = format!(" Tail: {} ({} servers) ", tail.source, tail.hosts.len());
+    if let Some(re) = &view.filter {
+        title.push_str(&format!("[filter: {}] ", re.as_str()));
+    }
+    if tail.paused {
+        title.push_str(&format!("[paused, {} new", tail.held()));
+        if tail.dropped() > 0 {
+            title.push_str(&format!(", {} older dropped", tail.dropped()));
+        }
+        title.push_str("] ");
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    // 从底部往上取一屏，scroll 为向上滚动的行数
+    let lines = view.visible();
+    let height = inner[0].height as usize;
+    let end = lines.len().saturating_sub(view.scroll.min(lines.len().saturating_sub(height)));
+    let start = end.saturating_sub(height);
+    let width = tail.hosts.iter().map(|h| h.name.len()).max().unwrap_or(0);
+    let text: Vec<Line> = lines[start..end]
+        .iter()
+        .map(|line| {
+            let style = if line.stderr { theme.error() } else { theme.text() };
+            Line::from(vec![
+                Span::styled(format!("{:<width$} | ", tail.hosts[line.host].name, width = width), theme.host(line.host)),
+                Span::styled(line.text.as_str(), style),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(text), inner[0]);
+
+    let footer = match (&view.filter_input, &view.notice) {
+        (Some(input), _) => {
+            f.set_cursor(inner[1].x + 8 + input.len() as u16, inner[1].y);
+            Paragraph::new(format!("Filter: {}", input)).style(theme.input())
+        }
+        (None, Some(notice)) => Paragraph::new(notice.as_str()).style(theme.notice()),
+        (None, None) => {
+            // 只列出已经结束的服务器，其余都在运行
+            let ended: Vec<String> = tail
+                .hosts
+                .iter()
+                .filter_map(|host| match &host.state {
+                    TailHostState::Running => None,
+                    TailHostState::Exited(Some(code)) => Some(format!("{} exited ({})", host.name, code)),
+                    TailHostState::Exited(None) => Some(format!("{} killed", host.name)),
+                    TailHostState::Error(e) => Some(format!("{}: {}", host.name, e)),
+                })
+                .collect();
+            let running = tail.hosts.len() - ended.len();
+            let mut status = format!("{} running", running);
+            if !ended.is_empty() {
+                status.push_str(&format!(" | {}", ended.join(" | ")));
+            }
+            let style = if ended.is_empty() { theme.hint() } else { theme.error() };
+            Paragraph::new(status).style(style)
+        }
+    };
+    f.render_widget(footer, inner[1]);
+}
+
+/// Returns the list area and its scroll offset.
+fn render_watch(f: &mut Frame, theme: &Theme, view: &WatchView) -> (Rect, usize) {
+    let size = f.size();
+    let watch = &view.watch;
+    let area = centered_fixed_rect(80, size.height.saturating_sub(4), size);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Watch: {} ", watch.command))
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    let state = match watch.next_in() {
+        _ if watch.paused => "paused".to_string(),
+        Some(left) => format!("next run in {}s", left.as_secs() + 1),
+        None => "running".to_string(),
+    };
+    let header = Paragraph::new(format!(
+        "Every {}s | Round {} | {} changed | {}",
+        watch.interval.as_secs(),
+        watch.round,
+        watch.changed(),
+        state
+    ))
+    .style(theme.text());
+    f.render_widget(header, inner[0]);
+
+    let items: Vec<ListItem> = watch
+        .hosts
+        .iter()
+        .map(|host| {
+            let (status, style) = status_label(theme, &host.run.status);
+            // 本轮输出有变化的主机用 * 标出并高亮
+            let (marker, style) = if host.changed { ("*", theme.notice()) } else { (" ", style) };
+            let since = host
+                .changed_at
+                .map(|at| format!("{}s ago", at.elapsed().as_secs()))
+                .unwrap_or_else(|| "-".to_string());
+            ListItem::new(format!(
+                "{}{:<7} {:<20} {:>9}  {}",
+                marker,
+                status,
+                host.run.name,
+                since,
+                first_line(&host.run)
+            ))
+            .style(style)
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(theme.highlight())
+        .highlight_symbol("> ");
+    let mut list_state = ListState::default().with_selected(Some(view.cursor));
+    f.render_stateful_widget(list, inner[1], &mut list_state);
+
+    let hint = Paragraph::new("* changed in the last round | column: time since the last change")
+        .style(theme.hint());
+    f.render_widget(hint, inner[2]);
+
+    if let (Some(scroll), Some(host)) = (view.output_scroll, watch.hosts.get(view.cursor)) {
+        render_watch_output(f, theme, host, scroll);
+    }
+
+    (inner[1], list_state.offset())
+}
+
+/// Latest output of a watched host, with the lines that changed since the round before.
+fn render_watch_output(f: &mut Frame, theme: &Theme, host: &WatchHost, scroll: u16) {
+    let size = f.size();
+    let area = centered_fixed_rect(90, size.height.saturating_sub(2), size);
+
+    let mut lines: Vec<Line> = match (&host.previous, host.changed) {
+        (Some((_, previous)), true) => diff_lines(previous, &host.run.stdout)
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(l) => Line::from(format!("  {}", l)),
+                DiffLine::Removed(l) => Line::styled(format!("- {}", l), theme.error()),
+                DiffLine::Added(l) => Line::styled(format!("+ {}", l), theme.selected()),
+            })
+            .collect(),
+        _ => host.run.stdout.lines().map(|l| Line::from(l.to_string())).collect(),
+    };
+    lines.extend(host.run.stderr.lines().map(|l| Line::styled(l.to_string(), theme.error())));
+    if lines.is_empty() {
+        lines.push(Line::styled("(no output)", theme.placeholder()));
+    }
+
+    let changes = if host.changed { ", changed since the last round" } else { "" };
+    let output = Paragraph::new(lines)
+        .style(theme.text())
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border())
+                .title(format!(" {} ({}{}) ", host.run.name, status_text(&host.run.status), changes)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(output, area);
+}
+
+fn status_label(theme: &Theme, status: &HostStatus) -> (String, Style) {
+    match status {
+        HostStatus::Pending => ("  ...".to_string(), theme.placeholder()),
+        HostStatus::Running => ("  run".to_string(), theme.notice()),
+        HostStatus::Exited(0) => ("ok  0".to_string(), theme.selected()),
+        HostStatus::Exited(code) => (format!("ERR {}", code), theme.error()),
+        HostStatus::Killed => ("ERR  ".to_string(), theme.error()),
+        HostStatus::Error(_) => ("ERR  ".to_string(), theme.error()),
+        HostStatus::Skipped => ("skip ".to_string(), theme.placeholder()),
+    }
+}
+
+fn status_text(status: &HostStatus) -> String {
+    match status {
+        HostStatus::Exited(code) => format!("exit {}", code),
+        HostStatus::Killed => "killed by signal".to_string(),
+        HostStatus::Error(e) => e.clone(),
+        HostStatus::Pending | HostStatus::Running => "running".to_string(),
+        HostStatus::Skipped => "skipped".to_string(),
+    }
+}
+
+// 列表中只显示第一行输出，Enter 查看完整输出
+fn first_line(host: &HostRun) -> &str {
+    match &host.status {
+        HostStatus::Error(e) => e.as_str(),
+        _ => host
+            .stdout
+            .lines()
+            .chain(host.stderr.lines())
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or(""),
+    }
+}
+
+fn render_host_output(f: &mut Frame, theme: &Theme, host: &HostRun, title: &str, scroll: u16) {
+    let size = f.size();
+    let area = centered_fixed_rect(90, size.height.saturating_sub(2), size);
+
+    let mut lines: Vec<Line> = host.stdout.lines().map(|l| Line::from(l.to_string())).collect();
+    lines.extend(host.stderr.lines().map(|l| Line::styled(l.to_string(), theme.error())));
+    if lines.is_empty() {
+        lines.push(Line::styled("(no output)", theme.placeholder()));
+    }
+
+    let output = Paragraph::new(lines)
+        .style(theme.text())
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border())
+                .title(format!(" {} ({}) ", title, status_text(&host.status))),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(output, area);
+}
+
+/// Diff of the stdout of two groups, each represented by one of its hosts.
+fn render_output_diff(f: &mut Frame, theme: &Theme, rollout: &Rollout, old: usize, new: usize, scroll: u16) {
+    let size = f.size();
+    let area = centered_fixed_rect(90, size.height.saturating_sub(2), size);
+    let (old, new) = (&rollout.hosts[old], &rollout.hosts[new]);
+
+    let mut lines: Vec<Line> = diff_lines(&old.stdout, &new.stdout)
+        .into_iter()
+        .map(|line| match line {
+            DiffLine::Same(l) => Line::from(format!("  {}", l)),
+            DiffLine::Removed(l) => Line::styled(format!("- {}", l), theme.error()),
+            DiffLine::Added(l) => Line::styled(format!("+ {}", l), theme.selected()),
+        })
+        .collect();
+    if old.status != new.status {
+        lines.insert(0, Line::styled(format!("+ ({})", status_text(&new.status)), theme.selected()));
+        lines.insert(0, Line::styled(format!("- ({})", status_text(&old.status)), theme.error()));
+    }
+
+    let output = Paragraph::new(lines)
+        .style(theme.text())
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border())
+                .title(format!(" - {} group / + {} group ", old.name, new.name)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(output, area);
+}
+
+/// Returns the list area and its scroll offset.
+fn render_run_history(f: &mut Frame, theme: &Theme, runs: &[Report], cursor: usize) -> (Rect, usize) {
+    let size = f.size();
+    let height = (runs.len().max(1) as u16 + 4).min(size.height.saturating_sub(4));
+    let area = centered_fixed_rect(80, height, size);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Broadcast Runs ")
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    if runs.is_empty() {
+        let empty = Paragraph::new("No broadcasts have been run yet.")
+            .style(theme.placeholder())
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, inner[0]);
+        f.render_widget(Paragraph::new("Esc: back").style(theme.hint()), inner[1]);
+        return (inner[0], 0);
+    }
+
+    let items: Vec<ListItem> = runs
+        .iter()
+        .map(|run| {
+            let failed = run.failed();
+            let style = if failed > 0 { theme.error() } else { theme.text() };
+            // 2024-05-01T12:30:00Z 显示为 2024-05-01 12:30:00
+            let time = run.timestamp.replace('T', " ").trim_end_matches('Z').to_string();
+            ListItem::new(format!(
+                "{}  {:>3} hosts  {:>3} failed  {}",
+                time,
+                run.hosts.len(),
+                failed,
+                run.command
+            ))
+            .style(style)
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(theme.highlight())
+        .highlight_symbol("> ");
+    let mut state = ListState::default().with_selected(Some(cursor));
+    f.render_stateful_widget(list, inner[0], &mut state);
+    let hint = Paragraph::new("Enter: show results | Esc: back").style(theme.hint());
     f.render_widget(hint, inner[1]);
+    (inner[0], state.offset())
 }
 
-fn render_profile_selection(f: &mut Frame, profiles: &[String], state: &mut ListState, current: &str) {
+/// Returns the list area and its scroll offset.
+fn render_snippet_list(f: &mut Frame, theme: &Theme, snippets: &[Snippet], cursor: usize) -> (Rect, usize) {
+    let size = f.size();
+    let height = (snippets.len().max(1) as u16 + 4).min(size.height.saturating_sub(4));
+    let area = centered_fixed_rect(60, height, size);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Broadcast Command - Snippets ")
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    if snippets.is_empty() {
+        let empty = Paragraph::new("No snippets yet. Type a command and press Ctrl+S to save it.")
+            .style(theme.placeholder())
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, inner[0]);
+    } else {
+        let items: Vec<ListItem> = snippets
+            .iter()
+            .map(|s| ListItem::new(format!("{}: {}", s.name, s.command)).style(theme.text()))
+            .collect();
+        let list = List::new(items)
+            .highlight_style(theme.highlight())
+            .highlight_symbol("> ");
+        let mut state = ListState::default().with_selected(Some(cursor));
+        f.render_stateful_widget(list, inner[0], &mut state);
+        let hint = Paragraph::new("Enter: use | d: delete | Esc: back").style(theme.hint());
+        f.render_widget(hint, inner[1]);
+        return (inner[0], state.offset());
+    }
+
+    let hint = Paragraph::new("Esc: back").style(theme.hint());
+    f.render_widget(hint, inner[1]);
+    (inner[0], 0)
+}
+
+fn render_profile_selection(
+    f: &mut Frame,
+    theme: &Theme,
+    profiles: &[String],
+    state: &mut ListState,
+    current: &str,
+) -> Rect {
     let size = f.size();
     let height = (profiles.len() as u16 + 4).min(size.height.saturating_sub(4));
     let area = centered_fixed_rect(40, height, size);
@@ -337,7 +1429,7 @@ fn render_profile_selection(f: &mut Frame, profiles: &[String], state: &mut List
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" Select Profile ")
-        .style(Style::default().fg(Color::Magenta));
+        .style(theme.profile());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
@@ -350,9 +1442,9 @@ fn render_profile_selection(f: &mut Frame, profiles: &[String], state: &mut List
     let items: Vec<ListItem> = profiles
         .iter()
         .map(|p| {
-            let mut style = Style::default();
+            let mut style = theme.text();
             let content = if p == current {
-                style = style.fg(Color::Cyan).add_modifier(Modifier::BOLD);
+                style = theme.current();
                 format!("* {}", p)
             } else {
                 p.clone()
@@ -362,23 +1454,25 @@ fn render_profile_selection(f: &mut Frame, profiles: &[String], state: &mut List
         .collect();
 
     let list = List::new(items)
-        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .highlight_style(theme.highlight())
         .highlight_symbol("> ");
     f.render_stateful_widget(list, inner[0], state);
 
     let hint = Paragraph::new("Enter: Load | n: New | Esc: Cancel")
-        .style(Style::default().fg(Color::Gray));
+        .style(theme.hint());
     f.render_widget(hint, inner[1]);
+
+    inner[0]
 }
 
-fn render_profile_creation(f: &mut Frame, name: &str) {
+fn render_profile_creation(f: &mut Frame, theme: &Theme, name: &str) {
     let size = f.size();
     let area = centered_fixed_rect(40, 7, size);
 
     let block = Block::default()
         .borders(Borders::ALL)
         .title(" New Profile ")
-        .style(Style::default().fg(Color::Magenta));
+        .style(theme.profile());
     f.render_widget(Clear, area);
     f.render_widget(block, area);
 
@@ -389,7 +1483,7 @@ fn render_profile_creation(f: &mut Frame, name: &str) {
         .split(area);
 
     let input = Paragraph::new(name)
-        .style(Style::default().fg(Color::Yellow))
+        .style(theme.input())
         .block(Block::default().borders(Borders::ALL).title("Profile Name"));
     f.render_widget(input, inner[0]);
 
@@ -397,6 +1491,40 @@ fn render_profile_creation(f: &mut Frame, name: &str) {
     f.set_cursor(inner[0].x + name.len() as u16 + 1, inner[0].y + 1);
 
     let hint = Paragraph::new("Enter: Create | Esc: Cancel")
-        .style(Style::default().fg(Color::Gray));
+        .style(theme.hint());
+    f.render_widget(hint, inner[1]);
+}
+
+fn render_help_overlay(f: &mut Frame, theme: &Theme, keymap: &Keymap) {
+    let size = f.size();
+    let actions = keymap.actions();
+    let height = (actions.len() as u16 + 4).min(size.height.saturating_sub(2));
+    let area = centered_fixed_rect(60, height, size);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Key Bindings ")
+        .style(theme.accent());
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    let items: Vec<ListItem> = actions
+        .into_iter()
+        .map(|action| {
+            let keys = keymap.keys_for(action).join(", ");
+            let content = format!("{:<20} {}", keys, keymap.description(action));
+            ListItem::new(content).style(theme.text())
+        })
+        .collect();
+    f.render_widget(List::new(items), inner[0]);
+
+    let hint = Paragraph::new("Remap keys in [keys] and add actions in [[actions]] of config.toml")
+        .style(theme.hint());
     f.render_widget(hint, inner[1]);
 }
@@ -0,0 +1,127 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Parse a port number in the range 1-65535.
+pub fn parse_port(value: &str) -> Result<u16, String> {
+    match value.trim().parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err("Port must be a number between 1 and 65535".to_string()),
+    }
+}
+
+/// Check that a host is a valid hostname, IPv4 or IPv6 address.
+pub fn check_host(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err("Host is required".to_string());
+    }
+    // IPv6 地址允许带方括号
+    let unbracketed = unbracket(value);
+    if unbracketed.contains(':') {
+        return unbracketed
+            .parse::<Ipv6Addr>()
+            .map(|_| ())
+            .map_err(|_| format!("'{}' is not a valid IPv6 address", value));
+    }
+    if value.parse::<Ipv4Addr>().is_ok() {
+        return Ok(());
+    }
+    check_hostname(value)
+}
+
+/// The host without the brackets around an IPv6 address; ssh wants `user@::1`, and only
+/// `host:port` forms need `[::1]`.
+pub fn unbracket(value: &str) -> &str {
+    value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value)
+}
+
+fn check_hostname(value: &str) -> Result<(), String> {
+    let name = value.strip_suffix('.').unwrap_or(value);
+    if name.len() > 253 {
+        return Err("Hostname is longer than 253 characters".to_string());
+    }
+    let labels: Vec<&str> = name.split('.').collect();
+    // 全数字的标签组合只可能是写错的 IPv4 地址
+    if labels.iter().all(|l| !l.is_empty() && l.chars().all(|c| c.is_ascii_digit())) {
+        return Err(format!("'{}' is not a valid IPv4 address", value));
+    }
+    for label in labels {
+        let valid = !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!("'{}' is not a valid hostname", value));
+        }
+    }
+    Ok(())
+}
+
+/// Check a login name. It ends up on the ssh command line, so only characters that can
+/// not be taken for an option or shell syntax are accepted; empty means inherited.
+pub fn check_user(value: &str) -> Result<(), String> {
+    if value.starts_with('-') {
+        return Err(format!("User '{}' must not start with '-'", value));
+    }
+    if !value.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '$')) {
+        return Err(format!("User '{}' may only contain letters, digits, '.', '_', '-' and '$'", value));
+    }
+    Ok(())
+}
+
+/// Check an ssh option given as `Key=Value`, as passed to `ssh -o`.
+pub fn check_ssh_option(option: &str) -> Result<(), String> {
+    match option.split_once('=') {
+        Some((key, _)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) => Ok(()),
+        _ => Err(format!("'{}' is not in Key=Value form", option)),
+    }
+}
+
+/// Check a jump host specification as accepted by `ssh -J`:
+/// a comma-separated list of `[user@]host[:port]` entries.
+pub fn check_jump_host(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    for hop in value.split(',') {
+        check_jump_hop(hop.trim())
+            .map_err(|e| format!("Jump host: {} (expected user@host:port)", e))?;
+    }
+    Ok(())
+}
+
+fn check_jump_hop(hop: &str) -> Result<(), String> {
+    let host_port = match hop.rsplit_once('@') {
+        Some((user, rest)) => {
+            if user.is_empty() {
+                return Err("invalid user".to_string());
+            }
+            check_user(user)?;
+            rest
+        }
+        None => hop,
+    };
+    let (host, port) = split_host_port(host_port);
+    check_host(host)?;
+    if let Some(port) = port {
+        parse_port(port)?;
+    }
+    Ok(())
+}
+
+/// Split `host:port`, `[v6]:port`, `host` or a bare IPv6 address.
+pub fn split_host_port(value: &str) -> (&str, Option<&str>) {
+    if value.starts_with('[') {
+        if let Some(end) = value.find(']') {
+            let port = value[end + 1..].strip_prefix(':');
+            return (&value[..=end], port);
+        }
+    }
+    match value.split_once(':') {
+        // 多个冒号说明是未加括号的 IPv6 地址
+        Some((host, port)) if !port.contains(':') => (host, Some(port)),
+        _ => (value, None),
+    }
+}